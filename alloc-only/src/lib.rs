@@ -0,0 +1,76 @@
+//! Uses try_utils with the `alloc` feature but without `std`. Building
+//! this crate (outside of tests, which need `std` for the harness) checks
+//! that nothing pulls `std` in.
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use try_utils::*;
+
+#[derive(Debug, PartialEq)]
+pub enum Reading {
+    Valid(u16),
+    Invalid(u8),
+}
+
+/// Splits readings into the valid values and the rejected readings.
+pub fn partition(readings: Vec<Reading>) -> (Vec<u16>, Vec<Reading>) {
+    let mut rejected = Vec::new();
+    let valid = collect_inner!(readings, if Reading::Valid, into rejected);
+    (valid, rejected)
+}
+
+/// Describes why a reading is not valid.
+pub fn check(reading: Reading) -> Result<u16, String> {
+    ok!(reading, if Reading::Valid, or_fmt "invalid reading {:?}", reading)
+}
+
+/// Returns the first value, or zero, without allocating.
+pub fn first_or_zero(values: &[u16]) -> u16 {
+    *first_inner!(values, else &0)
+}
+
+/// Descends into a reading, panicking on an invalid one.
+pub fn expect_valid(reading: Reading) -> u16 {
+    inner!(reading, if Reading::Valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn alloc_helpers() {
+        let (valid, rejected) = partition(vec![
+            Reading::Valid(1),
+            Reading::Invalid(2),
+            Reading::Valid(3),
+        ]);
+        assert_eq!(valid, vec![1, 3]);
+        assert_eq!(rejected, vec![Reading::Invalid(2)]);
+
+        assert_eq!(check(Reading::Valid(4)), Ok(4));
+        assert_eq!(
+            check(Reading::Invalid(5)),
+            Err("invalid reading Invalid(5)".into())
+        );
+    }
+
+    #[test]
+    fn core_macros() {
+        assert_eq!(first_or_zero(&[]), 0);
+        assert_eq!(first_or_zero(&[7, 8]), 7);
+        assert_eq!(expect_valid(Reading::Valid(9)), 9);
+        assert_eq!(some!(Reading::Invalid(1), if Reading::Valid), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected 'Reading::Valid' inside 'reading'")]
+    fn core_panic() {
+        expect_valid(Reading::Invalid(0));
+    }
+}
@@ -0,0 +1,21 @@
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 64K
+}
+
+ENTRY(reset);
+
+SECTIONS
+{
+  .text ORIGIN(FLASH) :
+  {
+    LONG(ORIGIN(RAM) + LENGTH(RAM));
+    LONG(reset | 1);
+    *(.text .text.*);
+  } > FLASH
+
+  .rodata : { *(.rodata .rodata.*); } > FLASH
+
+  /DISCARD/ : { *(.ARM.exidx .ARM.exidx.*); }
+}
@@ -0,0 +1,101 @@
+//! A firmware image that only runs a few panicking `inner!` forms, to
+//! compare its size with and without the `defmt` feature of try_utils:
+//!
+//! ```text
+//! cargo build --release --target thumbv7em-none-eabi
+//! cargo build --release --target thumbv7em-none-eabi --features defmt
+//! ```
+//!
+//! Both builds render a failed extraction to the same output register:
+//! through `core::fmt` without `defmt`, and as a `defmt` frame with it.
+
+#![no_std]
+#![no_main]
+// The panic handlers have nowhere to go, and halt.
+#![allow(clippy::empty_loop)]
+
+use core::ptr;
+use try_utils::{assert_inner_eq, inner};
+
+const INPUT: *const u32 = 0x2000_0000 as *const u32;
+const OUTPUT: *mut u8 = 0x4000_0000 as *mut u8;
+
+#[allow(dead_code)]
+enum Command {
+    Move(u32),
+    Stop(u16),
+}
+
+#[no_mangle]
+pub extern "C" fn reset() -> ! {
+    loop {
+        // SAFETY: on the imagined target, `INPUT` is a readable register.
+        let n = unsafe { ptr::read_volatile(INPUT) };
+        let cmd = if n % 2 == 0 {
+            Command::Move(n)
+        } else {
+            Command::Stop(n as u16)
+        };
+        assert_inner_eq!(cmd, Command::Move, n);
+        let steps = inner!(cmd, if Command::Move);
+        let c = inner!(core::char::from_u32(steps));
+        // SAFETY: `OUTPUT` is a writable register.
+        unsafe { ptr::write_volatile(OUTPUT, c as u8) };
+    }
+}
+
+#[cfg(not(feature = "defmt"))]
+struct Output;
+
+#[cfg(not(feature = "defmt"))]
+impl core::fmt::Write for Output {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for b in s.bytes() {
+            // SAFETY: `OUTPUT` is a writable register.
+            unsafe { ptr::write_volatile(OUTPUT, b) };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "defmt"))]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    let _ = core::fmt::Write::write_fmt(&mut Output, format_args!("{}", info));
+    loop {}
+}
+
+#[cfg(feature = "defmt")]
+#[defmt::global_logger]
+struct Logger;
+
+#[cfg(feature = "defmt")]
+// SAFETY: there is a single thread and no interrupts.
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {}
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {}
+
+    unsafe fn write(bytes: &[u8]) {
+        for &b in bytes {
+            ptr::write_volatile(OUTPUT, b);
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+#[defmt::panic_handler]
+fn defmt_panic() -> ! {
+    loop {}
+}
+
+// Like `panic-probe` with `print-defmt`: a panic that does not go through
+// `defmt` is still rendered with `core::fmt`.
+#[cfg(feature = "defmt")]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo<'_>) -> ! {
+    defmt::error!("{}", defmt::Display2Format(info));
+    loop {}
+}
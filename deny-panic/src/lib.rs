@@ -0,0 +1,87 @@
+//! Builds with the `deny_panic` feature of try_utils, to check that only
+//! the panicking forms are affected by it.
+//!
+//! ```compile_fail
+//! let x: Option<i32> = None;
+//! let _ = try_utils::inner!(x);
+//! ```
+//!
+//! ```compile_fail
+//! let x: Option<i32> = Some(1);
+//! let _ = try_utils::inner!(x, if Some);
+//! ```
+//!
+//! The other macros descending without an `else` clause are refused too:
+//!
+//! ```compile_fail
+//! let x: Option<Box<i32>> = None;
+//! let _ = try_utils::deref_inner!(x);
+//! ```
+//!
+//! ```compile_fail
+//! let mut x: Option<i32> = Some(1);
+//! let _ = try_utils::replace_inner!(&mut x, if Some, None);
+//! ```
+//!
+//! ```compile_fail
+//! let cell = std::cell::RefCell::new(Some(1));
+//! let _ = try_utils::borrow_inner!(cell, if Some);
+//! ```
+
+#[cfg(test)]
+use try_utils::*;
+
+#[cfg(test)]
+#[derive(Debug, PartialEq)]
+enum Fruit {
+    Apple(i32),
+    Orange(i16),
+}
+
+#[test]
+fn else_forms() {
+    let x: Option<i32> = None;
+    assert_eq!(inner!(x, else 3), 3);
+    assert_eq!(inner!(Some(1), else |_e| 3), 1);
+
+    let r: Result<i32, &str> = Err("bad");
+    assert_eq!(inner!(r, else |e| e.len() as i32), 3);
+
+    let z = Fruit::Orange(2);
+    assert_eq!(inner!(z, if Fruit::Apple, else 0), 0);
+    let z = Fruit::Apple(2);
+    assert_eq!(inner!(&z, if Fruit::Apple, else |_e| &0), &2);
+    assert_eq!(inner!(if let Some(v) = Some(1) => v + 1, else 0), 2);
+}
+
+#[test]
+fn ok_and_some() {
+    assert_eq!(ok!(Fruit::Apple(1), if Fruit::Apple), Ok(1));
+    assert_eq!(
+        ok!(Fruit::Orange(1), if Fruit::Apple),
+        Err(Fruit::Orange(1))
+    );
+    assert_eq!(some!(Fruit::Apple(1), if Fruit::Apple), Some(1));
+    assert_eq!(some!(Fruit::Orange(1), if Fruit::Apple), None);
+}
+
+#[test]
+fn other_macros() {
+    fn check(state: &Fruit) -> Result<(), ()> {
+        ensure_inner!(state, if Fruit::Apple, else return Err(()));
+        Ok(())
+    }
+    assert_eq!(check(&Fruit::Apple(1)), Ok(()));
+    assert_eq!(check(&Fruit::Orange(1)), Err(()));
+
+    let v: Vec<i32> = vec![];
+    assert_eq!(first_inner!(v, else 7), 7);
+    let r: Result<i32, ()> = swap_variant!(Some(3), Some => Ok, else Err(()));
+    assert_eq!(r, Ok(3));
+    assert_eq!(deref_inner!(Some(Box::new(4)), else 0), 4);
+    let mut z = Fruit::Apple(5);
+    assert_eq!(
+        replace_inner!(&mut z, if Fruit::Apple, Fruit::Orange(0), else 0),
+        5
+    );
+}
@@ -0,0 +1,217 @@
+//! Derive macro for the `IntoResult` trait of the `try_utils` crate, and
+//! the `impl_inner_ext!` generator.
+//!
+//! Use it through `try_utils` with the `derive` feature enabled, rather than
+//! depending on this crate directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Fields, Ident, Path, Token, Type, Visibility,
+};
+
+/// Implements `IntoResult<T, Self>` for an enum.
+///
+/// Variants marked `#[ok]` become `Ok` with their payload, everything else
+/// becomes `Err(self)`. Variants marked `#[ok(into)]` have their payload
+/// converted with `Into::into`, so that variants with differing payload
+/// types can share a common `T`.
+///
+/// `T` is the payload type of the first `#[ok]` variant, unless given
+/// explicitly with `#[into_result(ok = Type)]` on the enum.
+#[proc_macro_derive(IntoResult, attributes(ok, into_result))]
+pub fn derive_into_result(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "IntoResult can only be derived for enums",
+            ))
+        }
+    };
+
+    let mut ok_type: Option<Type> = None;
+    for attr in &input.attrs {
+        if attr.path().is_ident("into_result") {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("ok") {
+                    ok_type = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `ok = Type`"))
+                }
+            })?;
+        }
+    }
+
+    let name = &input.ident;
+    let mut arms = Vec::new();
+    for variant in &data.variants {
+        let mut is_ok = false;
+        let mut into = false;
+        for attr in &variant.attrs {
+            if !attr.path().is_ident("ok") {
+                continue;
+            }
+            is_ok = true;
+            if let syn::Meta::List(_) = attr.meta {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("into") {
+                        into = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("expected `into`"))
+                    }
+                })?;
+            }
+        }
+        if !is_ok {
+            continue;
+        }
+
+        let field = match &variant.fields {
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => &fields.unnamed[0],
+            _ => {
+                return Err(Error::new_spanned(
+                    variant,
+                    "#[ok] variants must have exactly one unnamed field",
+                ))
+            }
+        };
+        if ok_type.is_none() {
+            ok_type = Some(field.ty.clone());
+        }
+
+        let ident = &variant.ident;
+        arms.push(if into {
+            quote! { #name::#ident(q) => ::core::result::Result::Ok(::core::convert::Into::into(q)), }
+        } else {
+            quote! { #name::#ident(q) => ::core::result::Result::Ok(q), }
+        });
+    }
+
+    let ok_type = match ok_type {
+        Some(ty) => ty,
+        None => {
+            return Err(Error::new_spanned(
+                name,
+                "IntoResult needs at least one variant marked #[ok]",
+            ))
+        }
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::try_utils::IntoResult<#ok_type, Self> for #name #ty_generics #where_clause {
+            #[inline]
+            fn into_result(self) -> ::core::result::Result<#ok_type, Self> {
+                match self {
+                    #(#arms)*
+                    other => ::core::result::Result::Err(other),
+                }
+            }
+        }
+    })
+}
+
+/// Generates an extension trait with a method that takes the payloads of
+/// one variant out of an iterator.
+///
+/// `impl_inner_ext!(Fruit, Apple, i32)` defines `trait FruitAppleExt` with
+/// `fn apples(self) -> impl Iterator<Item = i32>`, for every iterator of
+/// `Fruit`. The method is named after the variant in snake case, with an
+/// `s` appended. A visibility in front of the enum applies to the trait.
+#[proc_macro]
+pub fn impl_inner_ext(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as InnerExt);
+    expand_inner_ext(input).into()
+}
+
+struct InnerExt {
+    vis: Visibility,
+    ty: Path,
+    variant: Ident,
+    payload: Type,
+}
+
+impl Parse for InnerExt {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        let ty = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let variant = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let payload = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(InnerExt {
+            vis,
+            ty,
+            variant,
+            payload,
+        })
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn expand_inner_ext(input: InnerExt) -> TokenStream2 {
+    let InnerExt {
+        vis,
+        ty,
+        variant,
+        payload,
+    } = input;
+    let ty_name = &ty.segments.last().expect("paths have a segment").ident;
+    let trait_name = format_ident!("{}{}Ext", ty_name, variant);
+    let method = format_ident!(
+        "{}s",
+        snake_case(&variant.to_string()),
+        span = variant.span()
+    );
+    // Patterns would need a turbofish for generic arguments, and they are
+    // known from the item type anyway.
+    let mut pattern = ty.clone();
+    for segment in &mut pattern.segments {
+        segment.arguments = syn::PathArguments::None;
+    }
+    quote! {
+        #vis trait #trait_name: ::core::iter::Iterator<Item = #ty> + ::core::marker::Sized {
+            fn #method(self) -> impl ::core::iter::Iterator<Item = #payload>;
+        }
+
+        impl<I: ::core::iter::Iterator<Item = #ty>> #trait_name for I {
+            #[inline]
+            fn #method(self) -> impl ::core::iter::Iterator<Item = #payload> {
+                ::core::iter::Iterator::filter_map(self, |x| match x {
+                    #pattern::#variant(q) => ::core::option::Option::Some(q),
+                    _ => ::core::option::Option::None,
+                })
+            }
+        }
+    }
+}
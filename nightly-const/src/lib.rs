@@ -0,0 +1,69 @@
+//! Uses the `nightly` feature of try_utils, whose `IntoResult` is a const
+//! trait, so these need a nightly compiler.
+
+#![feature(const_trait_impl)]
+
+use try_utils::*;
+
+/// The port to listen on, which has to be known at compile time.
+pub const fn port(configured: Option<u16>) -> u16 {
+    inner!(configured, else 8080)
+}
+
+pub const fn required(configured: Option<u16>) -> u16 {
+    inner!(const configured)
+}
+
+/// The default, formatted panic is still what runs outside of const.
+pub fn lookup(configured: Option<u16>) -> u16 {
+    inner!(configured)
+}
+
+pub const fn parsed(r: Result<u8, ()>) -> u8 {
+    inner!(r, else |_e| 0)
+}
+
+pub const fn extract(x: Option<i32>) -> i32 {
+    inner!(x, else { 0 })
+}
+
+pub const PORT: u16 = port(None);
+pub const TLS_PORT: u16 = required(Some(8443));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn const_into_result() {
+        const PARSED: u8 = parsed(Ok(7));
+        const FAILED: u8 = parsed(Err(()));
+        assert_eq!((PORT, TLS_PORT), (8080, 8443));
+        assert_eq!((PARSED, FAILED), (7, 0));
+
+        const V: i32 = extract(Some(42));
+        const MISSING: i32 = extract(None);
+        assert_eq!((V, MISSING), (42, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected value found inside 'configured'")]
+    fn const_panic() {
+        required(None);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Unexpected value found inside 'configured' (type core::option::Option<u16>)"
+    )]
+    fn runtime_panic() {
+        lookup(None);
+    }
+
+    // Types without a const implementation still work outside of const.
+    #[test]
+    fn non_const_impl() {
+        let r: &Result<i32, ()> = &Ok(3);
+        assert_eq!(*inner!(r), 3);
+    }
+}
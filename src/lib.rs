@@ -30,6 +30,22 @@
 //! thread "test" panicked at "Unexpected value found inside "z"", src/lib.rs:23
 //! ```
 //!
+//! The message names the type of the value, and if the unexpected value
+//! (or, for `Result`, the `Err` payload) implements `Debug`, it is
+//! included as well, e g
+//! `Unexpected value found inside 'z' (type core::result::Result<i32, &str>): "bad input"`.
+//!
+//! When descending into a variant with an `if` clause (see below), the
+//! message names the expected variant instead:
+//! `Expected 'Fruit::Apple' inside 'z' (type my_crate::Fruit), found Orange(15)`.
+//! Tests matching on panic messages can rely on these two formats, though
+//! not on the exact spelling of the type name, which comes from
+//! `core::any::type_name`. The expression is put on one line, and if it is
+//! longer than `MAX_EXPR_LEN` characters, it is cut at compile time and
+//! ends in `…`, so that long chains
+//! (or literals that should not end up in logs) are kept out of the
+//! message.
+//!
 //! # Error handling
 //! If panic isn't an option - and it usually isn't - just add an `else` clause:
 //!
@@ -46,6 +62,25 @@
 //! You can use the else clause to compute a default value, or use flow control
 //! (e g `break`, `continue`, or `return`).
 //!
+//! For example, to spin until a `Mutex` can be locked without blocking:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! # let mutex = std::sync::Mutex::new(0);
+//! let mut guard = loop {
+//!     break inner!(mutex.try_lock(), else {
+//!         std::thread::yield_now();
+//!         continue;
+//!     });
+//! };
+//! *guard += 1;
+//! # }
+//! ```
+//!
+//! Note that `inner!` itself does not back off or yield; whatever waiting
+//! strategy you want goes into the `else` clause.
+//!
 //! Want access to what's inside the `Err` value in your `else` clause?
 //! No problem, just add a `|variable|` after `else`, like this:
 //!
@@ -64,6 +99,126 @@
 //! Note: This does not turn your else clause into a closure, so you can still use
 //! (e g) `return` the same way as before.
 //!
+//! Like a closure argument, the variable can be a pattern, to take apart an
+//! error that is a tuple or a struct. This holds for the `else` and `or`
+//! clauses of the other macros as well:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let x: Result<String, (i32, String)> = Err((404, "not found".to_string()));
+//! let body = inner!(x, else |(code, msg)| format!("{}: {}", code, msg));
+//! assert_eq!(body, "404: not found");
+//! # }
+//! ```
+//!
+//! That includes `break` and `continue`, e g to stop reading at the first
+//! line that fails:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! use std::io::BufRead;
+//!
+//! let input: &[u8] = b"first\nsecond\n\xff\nfourth\n";
+//! let mut read = Vec::new();
+//! for line in input.lines() {
+//!     let line = inner!(line, else |e| {
+//!         eprintln!("stopped reading: {}", e);
+//!         break;
+//!     });
+//!     read.push(line);
+//! }
+//! assert_eq!(read, ["first", "second"]);
+//! # }
+//! ```
+//!
+//! For `Option`, and any other `IntoResult<T, ()>` implementation, the
+//! variable would only ever hold `()`. With an `Option`, an `err_with`
+//! clause can make up a more useful error instead, which is only called
+//! for `None`:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! # #[derive(Debug)]
+//! # enum MyErr { Missing }
+//! fn lookup(key: &str) -> Result<u32, MyErr> {
+//!     let value = std::env::var(key).ok();
+//!     let value = inner!(value, err_with || MyErr::Missing, else |e| return Err(e));
+//!     Ok(value.len() as u32)
+//! }
+//! # assert!(lookup("TRY_UTILS_SURELY_UNSET").is_err());
+//! # }
+//! ```
+//!
+//! Otherwise, use the plain `else` clause;
+//! if a binding is needed anyway (e g in a macro that always passes one),
+//! name it `_e` to avoid the unused variable warning:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let x: Option<i32> = None;
+//! let y = inner!(x, else 0);
+//! let z = inner!(x, else |_e| 0);
+//! assert_eq!(y, z);
+//! # }
+//! ```
+//!
+//! A `Result<T, Infallible>`, such as what parsing a `String` gives, can never be `Err`, so the panicking form never
+//! panics. To state that in the code, match the error with an empty match
+//! in the `else` clause, which the compiler then checks:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! use std::convert::Infallible;
+//!
+//! let r: Result<String, Infallible> = "seven".parse();
+//! assert_eq!(inner!(r, else |e| match e {}), "seven");
+//! # }
+//! ```
+//!
+//! Macros cannot see types, so there is no warning for a fallback in the
+//! `else` clause that can never run.
+//!
+//! The first argument can be any expression, including method calls that
+//! borrow, such as looking up a key in a map. The references stay tied to
+//! the map, so an `else` value must live as long:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! use std::collections::BTreeMap;
+//!
+//! let mut ports = BTreeMap::new();
+//! ports.insert("http", 80);
+//! let default_port = 8080;
+//! assert_eq!(*inner!(ports.get("http"), else &default_port), 80);
+//! assert_eq!(*inner!(ports.get("gopher"), else &default_port), 8080);
+//! # }
+//! ```
+//!
+//! Upgrading a `Weak` reference gives an `Option` as well, the same for
+//! `Rc` and `Arc`:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! use std::rc::Rc;
+//! use std::sync::Arc;
+//!
+//! let local = Rc::new(1);
+//! let shared = Arc::new(2);
+//! let (local_weak, shared_weak) = (Rc::downgrade(&local), Arc::downgrade(&shared));
+//!
+//! let local_count: Rc<i32> = inner!(local_weak.upgrade(), else return);
+//! let shared_count: Arc<i32> = inner!(shared_weak.upgrade(), else return);
+//! assert_eq!(*local_count + *shared_count, 3);
+//! # }
+//! ```
+//!
 //! # It works with your enums too
 //! It does not work only with `Option` and `Result`. Just add an `if` clause:
 //!
@@ -87,6 +242,183 @@
 //! You can skip the `else` clause to panic in case the enum is not
 //! the expected variant.
 //!
+//! The variant can also be named through a type alias, as in
+//! `inner!(x, if F::Apple)` after `type F = Fruit;`. It has to be a tuple
+//! variant though: an associated constant cannot take its place, since
+//! there is no payload to bind. Match such constants with `if let` instead.
+//!
+//! Given a reference to an enum, you get a reference to the payload, borrowed
+//! for as long as the enum is, so no explicit lifetimes are needed:
+//!
+//! ```
+//! # use try_utils::*;
+//! # enum Fruit {
+//! #     Apple(i32),
+//! #     Orange(i16),
+//! # }
+//! fn apple_weight<'a>(x: &'a Fruit) -> &'a i32 {
+//!     inner!(x, if Fruit::Apple)
+//! }
+//! # fn main() {
+//! # assert_eq!(apple_weight(&Fruit::Apple(3)), &3);
+//! # let _ = Fruit::Orange(0);
+//! # }
+//! ```
+//!
+//! A projection after the variant takes a field of the payload, or a
+//! chain of them, leaving the rest behind. With `ref`, it gives a
+//! reference to just that field, which is what you want for a borrowed
+//! enum. The `else` clause still gets the whole value:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! struct Pos {
+//!     x: i32,
+//!     y: i32,
+//! }
+//!
+//! struct Click {
+//!     pos: Pos,
+//!     button: u8,
+//! }
+//!
+//! enum Event {
+//!     Click(Click),
+//!     Key(char),
+//! }
+//!
+//! let ev = Event::Click(Click { pos: Pos { x: 3, y: 4 }, button: 1 });
+//! assert_eq!(inner!(&ev, if Event::Click, .pos.x), 3);
+//! let pos: &Pos = inner!(&ev, if Event::Click, ref .pos);
+//! assert_eq!(pos.y, 4);
+//! assert_eq!(inner!(Event::Key('q'), if Event::Click, .button, else 0), 0);
+//! # let _ = pos.x;
+//! # }
+//! ```
+//!
+//! A `guard` clause adds a condition on the payload, which is handed to it
+//! like a closure argument. If the condition does not hold, the `else`
+//! clause runs as for any other variant:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! struct Apple {
+//!     freshness: i32,
+//! }
+//!
+//! enum Fruit {
+//!     Apple(Apple),
+//!     Orange(i16),
+//! }
+//!
+//! let z = Fruit::Apple(Apple { freshness: 0 });
+//! let apple = inner!(z, if Fruit::Apple, guard |a| a.freshness > 0, else return);
+//! # let _ = (apple, Fruit::Orange(0));
+//! # }
+//! ```
+//!
+//! The payload is only visible through that binding, which is required:
+//! `guard a.freshness > 0` would only see an `a` from around the call, so
+//! it is rejected at compile time. The condition can still read and change
+//! state from around the call as well. `some!` and `ok!` take the same
+//! clause.
+//!
+//! Without an `else` clause, a payload the guard rejects panics with the
+//! variant marked `(guard)`, as in "Expected 'Fruit::Apple (guard)' inside
+//! 'z'", to tell it apart from a value of another variant.
+//!
+//! For an `Option`, `filter` does the same without naming the variant, like
+//! `Option::filter` followed by `unwrap_or`:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let x = Some(-3);
+//! assert_eq!(inner!(x, filter |v| v > 0, else 0), 0);
+//! # }
+//! ```
+//!
+//! A `then` clause transforms the payload before it is returned, reading
+//! like a pipeline. It is only applied on a match, so the `else` clause
+//! has to give a value of the transformed type:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let name: Option<&str> = Some("  apple ");
+//! assert_eq!(inner!(name, then |v| v.trim().to_owned(), else String::new()), "apple");
+//! assert_eq!(inner!(Ok::<i32, ()>(3), then |n| n * 2), 6);
+//! # }
+//! ```
+//!
+//! If you would rather keep the `if let` syntax, you can. After the value,
+//! `if let` names the variant and its bindings, which come back as with
+//! the destructuring patterns below. The value is repeated after `=`, and
+//! has to be the same expression:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let x = Some(20);
+//! let y = inner!(x, if let Some(v) = x, else 0);
+//! assert_eq!(y, 20);
+//! # }
+//! ```
+//!
+//! For any other pattern, or to compute the value to return with the
+//! bindings in scope, put `if let` first and the value after `=>`:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let x = Some(20);
+//! let y = inner!(if let Some(v) = x => v + 1, else 0);
+//! assert_eq!(y, 21);
+//! # }
+//! ```
+//!
+//! A pattern can be given inside the variant to destructure the payload
+//! further. The bindings come back as a tuple shaped like the pattern, so
+//! the pattern may only consist of bindings and tuples of them:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! enum Event {
+//!     Move((i32, (i32, String))),
+//!     Quit,
+//! }
+//!
+//! let ev = Event::Move((3, (4, "north".to_string())));
+//! let (x, (y, dir)) = inner!(ev, if Event::Move((x, (y, dir))), else return);
+//! assert_eq!((x, y, dir.as_str()), (3, 4, "north"));
+//! # let _ = Event::Quit;
+//! # }
+//! ```
+//!
+//! For a variant with several fields, the leading ones can be bound and the
+//! rest skipped with `..`, without knowing how many there are:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! enum Shape {
+//!     Triple(i32, String, f64),
+//!     Point,
+//! }
+//!
+//! let t = Shape::Triple(1, "two".to_string(), 3.0);
+//! assert_eq!(inner!(t, if Shape::Triple(first, ..), else 0), 1);
+//! # let _ = Shape::Point;
+//! # }
+//! ```
+//!
+//! Other patterns, with `_`, `ref`, `mut`, literals or a `..` that is not
+//! at the end, are rejected at compile time; use the `if let` form above
+//! for those, which names the output explicitly.
+//!
 //! Note that in this case, the entire item (instead of the contents inside
 //! `Err`) is passed on to the `else` clause:
 //!
@@ -132,279 +464,7672 @@
 //! assert_eq!(9, inner!(Fruit::Apple(9)));
 //! ```
 //!
-//! # License
-//! Apache2.0/MIT
-
-/// Converts a value into a Result.
-/// You can implement this for your own types if you want
-/// to use the `inner!` macro in more ergonomic ways.
-pub trait IntoResult<T, E> {
-    fn into_result(self) -> Result<T, E>;
-}
-
-impl<T, E> IntoResult<T, E> for Result<T, E> {
-    #[inline]
-    fn into_result(self) -> Result<T, E> {
-        self
-    }
-}
-
-impl<T> IntoResult<T, ()> for Option<T> {
-    #[inline]
-    fn into_result(self) -> Result<T, ()> {
-        self.ok_or(())
-    }
-}
-
-/// The `try!` macro - see module level documentation for details.
-#[macro_export]
-macro_rules! inner {
-    ($x:expr, if $i:path, else |$e:ident| $b:expr) => {{
-        match $x {
-            $i(q) => q,
-            $e @ _ => $b,
-        }
-    }};
-
-    ($x:expr, if $i:path, else $b:expr) => {{
-        match $x {
-            $i(q) => q,
-            _ => $b,
-        }
-    }};
-
-    ($x:expr, else |$e:ident| $b:expr) => {{
-        use $crate::IntoResult;
-        match $x.into_result() {
-            Ok(q) => q,
-            Err($e) => $b,
-        }
-    }};
-
-    ($x:expr, else $b:expr) => {{
-        use $crate::IntoResult;
-        match $x.into_result() {
-            Ok(q) => q,
-            _ => $b,
-        }
-    }};
-
-    ($x:expr, if $i:path) => {{
-        match $x {
-            $i(q) => q,
-            _ => panic!("Unexpected value found inside '{}'", stringify!($x)),
-        }
-    }};
-
-    ($x:expr) => {{
-        use $crate::IntoResult;
-        match $x.into_result() {
-            Ok(q) => q,
-            _ => panic!("Unexpected value found inside '{}'", stringify!($x)),
-        }
-    }};
-}
-
-/// Converts your enum to an Option.
-///
-/// # Examples
-///
-/// ```ignore
-/// assert_eq!(some!(Fruit::Apple(15), if Fruit::Apple), Some(15));
-/// assert_eq!(some!(Fruit::Orange(5), if Fruit::Apple), None);
-/// ```
-#[macro_export]
-macro_rules! some {
-    ($x:expr, if $i:path, else |$e:ident| $b:expr) => {{
-        match $x {
-            $i(q) => Some(q),
-            $e @ _ => $b,
-        }
-    }};
-
-    ($x:expr, if $i:path, else $b:expr) => {{
-        match $x {
-            $i(q) => Some(q),
-            _ => $b,
-        }
-    }};
-
-    ($x:expr, if $i:path) => {{
-        match $x {
-            $i(q) => Some(q),
-            _ => None,
-        }
-    }};
-}
-
-/// Converts your enum to an Result.
-///
-/// # Examples
-///
-/// ```ignore
-/// assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple), Ok(15));
-/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple), Err(Fruit::Orange(5)));
-///
-/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple, or {75}), Err(75));
-/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple, else {Err(75)}), Err(75));
-/// ```
-#[macro_export]
-macro_rules! ok {
-    ($x:expr, if $i:path, else |$e:ident| $b:expr) => {{
-        match $x {
-            $i(q) => Ok(q),
-            $e @ _ => $b,
-        }
-    }};
-
-    ($x:expr, if $i:path, else $b:expr) => {{
-        match $x {
-            $i(q) => Ok(q),
-            _ => $b,
-        }
-    }};
+//! With the `derive` feature, the implementation above can be generated by
+//! marking the `Ok` variants with `#[ok(into)]` and adding
+//! `#[derive(IntoResult)]` to the enum. The `Err` side is then the whole
+//! unmatched value.
+//!
+//! # Compared to `if let`, `unwrap_or_else` and `?`
+//! Here is the same lookup written with each. It reads the weight of an
+//! apple, and gives up on anything else:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! enum Fruit {
+//!     Apple(i32),
+//!     Orange(i16),
+//! }
+//!
+//! fn weight(f: Fruit) -> Result<i32, String> {
+//!     let w = inner!(f, if Fruit::Apple, else return Err("not an apple".to_string()));
+//!     Ok(w * 2)
+//! }
+//! assert_eq!(weight(Fruit::Apple(3)), Ok(6));
+//! assert!(weight(Fruit::Orange(3)).is_err());
+//! # }
+//! ```
+//!
+//! With `if let`, it is the same match written out yourself. That is the
+//! better choice when the `else` branch grows long, or when several
+//! variants need handling:
+//!
+//! ```
+//! # enum Fruit {
+//! #     Apple(i32),
+//! #     Orange(i16),
+//! # }
+//! fn weight(f: Fruit) -> Result<i32, String> {
+//!     let w = if let Fruit::Apple(w) = f {
+//!         w
+//!     } else {
+//!         return Err("not an apple".to_string());
+//!     };
+//!     Ok(w * 2)
+//! }
+//! # fn main() {
+//! # assert_eq!(weight(Fruit::Apple(3)), Ok(6));
+//! # assert!(weight(Fruit::Orange(3)).is_err());
+//! # }
+//! ```
+//!
+//! For an `Option` or a `Result`, `unwrap_or_else` gives a fallback value
+//! just as well. But its fallback is a closure, so a `return`, `break` or
+//! `continue` in it would only leave the closure. Use it when the fallback
+//! is a value, and `inner!` when it is flow control:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! let readings = [Some(3), None, Some(5)];
+//!
+//! let with_default: Vec<i32> = readings.iter().map(|r| r.unwrap_or_else(|| 0)).collect();
+//! assert_eq!(with_default, [3, 0, 5]);
+//!
+//! let mut present = Vec::new();
+//! for r in readings {
+//!     present.push(inner!(r, else continue));
+//! }
+//! assert_eq!(present, [3, 5]);
+//! # }
+//! ```
+//!
+//! The `?` operator is shortest when the function returns the same kind
+//! of `Option` or `Result`, or an error that the `Err` converts into with
+//! `From`. It does not work on your own enums, though, and it has no
+//! fallback other than returning:
+//!
+//! ```
+//! fn double(x: Option<i32>) -> Option<i32> {
+//!     let v = x?;
+//!     Some(v * 2)
+//! }
+//! # fn main() {
+//! assert_eq!(double(Some(3)), Some(6));
+//! assert_eq!(double(None), None);
+//! # }
+//! ```
+//!
+//! Finally, `unwrap` and `expect` panic like `inner!` without an `else`
+//! clause, but their panic message does not say which expression failed.
+//!
+//! # Forbidding the panicking forms
+//! With the `deny_panic` feature enabled, the forms that panic are compile
+//! errors instead, which name the macro and point to the non-panicking
+//! alternative. These are `inner!`, `deref_inner!`, `inner_cow!`,
+//! `match_any_inner!`, `replace_inner!`, `borrow_inner!`,
+//! `borrow_inner_mut!` and the `json_*!` macros without an `else` clause,
+//! `inner_debug_checked!`, and `inner_file!`. Everything else works as
+//! before. Assertion macros such as `assert_inner_eq!` are meant to panic
+//! and are not affected.
+//!
+//! # Custom panic messages
+//! A program can replace the message of these panics with its own format,
+//! once, with `set_panic_formatter`. The formatter gets the expression,
+//! the expected variant, the type name, what was found and where.
+//!
+//! # Use in `const fn`
+//! The `if` clause forms of `inner!`, `some!` and `ok!` expand to plain
+//! matches, so with an `else`, `or` or `guard` clause they can be used in
+//! `const fn` and in the initializers of `const` items, as long as the
+//! clauses themselves are constant:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! enum Config {
+//!     Fixed(i32),
+//!     Dynamic,
+//! }
+//!
+//! const fn timeout(x: Config) -> i32 {
+//!     inner!(x, if Config::Fixed, else 60)
+//! }
+//! const TIMEOUT: i32 = timeout(Config::Dynamic);
+//! const RETRIES: Option<i32> = some!(Config::Fixed(3), if Config::Fixed);
+//! assert_eq!((TIMEOUT, RETRIES), (60, Some(3)));
+//! # }
+//! ```
+//!
+//! The panicking `if` clause form formats what was found, which is not
+//! possible in a constant. Put `const` in front of the value to get a
+//! constant panic instead, with a message that leaves out the type and
+//! what was found:
+//!
+//! ```
+//! # use try_utils::*;
+//! # enum Config { Fixed(i32), Dynamic }
+//! # fn main() {
+//! const fn extract(x: Config) -> i32 {
+//!     inner!(const x, if Config::Fixed)
+//! }
+//! const TIMEOUT: i32 = extract(Config::Fixed(30));
+//! # assert_eq!(TIMEOUT, 30);
+//! # let _ = Config::Dynamic;
+//! # }
+//! ```
+//!
+//! The forms without an `if` clause go through the `IntoResult` trait,
+//! and can therefore not be used in `const fn` on a stable compiler (see
+//! the `nightly` feature below). For an `Option` or a `Result`, name the
+//! variant instead, which gives the same expansion as a plain match:
+//!
+//! ```
+//! # use try_utils::*;
+//! # fn main() {
+//! const fn extract(x: Option<i32>) -> i32 {
+//!     inner!(x, if Some, else { 0 })
+//! }
+//! const V: i32 = extract(Some(42));
+//! assert_eq!(V, 42);
+//! # }
+//! ```
+//!
+//! Neither can the clauses that format or allocate, `or_fmt` and
+//! `context`, be used in `const fn`.
+//!
+//! # Panicking through `defmt`
+//! On embedded targets using [`defmt`](https://defmt.ferrous-systems.com),
+//! enable the `defmt` feature to have the panicking forms call
+//! `defmt::panic!` instead of `panic!`. The format string is then interned
+//! by `defmt` rather than stored in the binary, and none of the `core::fmt`
+//! formatting machinery is pulled in on behalf of the panic, which is
+//! usually the bulk of the size cost of a panic with arguments. Since the
+//! `defmt` macros refer to the `defmt` crate by name, your crate needs to
+//! depend on `defmt` directly, and have a `defmt` global logger.
+//!
+//! The `defmt-size` directory of the repository has a thumbv7em firmware
+//! image that only runs `assert_inner_eq!` and two panicking `inner!`
+//! forms. Built with `opt-level = "z"` and LTO on Rust 1.95, it comes to:
+//!
+//! | build           | `.text` | `.rodata` |
+//! |-----------------|--------:|----------:|
+//! | without `defmt` |  2480 B |     539 B |
+//! | with `defmt`    |   242 B |      98 B |
+//!
+//! CI measures both builds with `llvm-size`, and fails if a section grows
+//! more than 10% past this table.
+//!
+//! With `defmt`, the wrappers and errors of this crate (`TryNew`,
+//! `TryConvert`, `U32ToChar`, `RightIsOk`, `RichOption` and its
+//! `MissingValue`) implement `defmt::Format`, so they can be logged along
+//! with the mismatch. Some forms keep using `core::fmt` when they panic:
+//! `inner_file!` and the `json_*!` macros, whose `std` errors and JSON
+//! values can only be rendered with `core::fmt`, and `inner!(const ..)`,
+//! since `defmt::panic!` cannot be used in a constant. `const_inner!`
+//! only ever panics at compile time, so it adds nothing to the binary.
+//!
+//! # Logging with `log`
+//! With the `log` feature enabled, `else log <level>, <fallback>` logs the
+//! mismatch before evaluating the fallback. The level is one of `trace`,
+//! `debug`, `info`, `warn` or `error`, and the log target is the module the
+//! macro is used in:
+//!
+//! ```
+//! # use try_utils::*;
+//! # #[derive(Debug)]
+//! # enum Fruit { Apple(i32), Orange(i16) }
+//! # #[cfg(feature = "log")]
+//! # fn main() {
+//! # let config = std::collections::HashMap::<&str, u64>::new();
+//! # let z = Fruit::Orange(15);
+//! let timeout = inner!(config.get("timeout"), else log warn, &30);
+//! // unexpected value found inside 'config.get("timeout")'
+//! let n = inner!(z, if Fruit::Apple, else log debug, 0);
+//! // expected 'Fruit::Apple' inside 'z', found Orange(15)
+//! # assert_eq!((*timeout, n), (30, 0));
+//! # let _ = Fruit::Apple(0);
+//! # }
+//! # #[cfg(not(feature = "log"))]
+//! # fn main() {}
+//! ```
+//!
+//! The message, including the `Debug` rendering of what was found, is only
+//! formatted if the level is enabled.
+//!
+//! # Structured events with `tracing`
+//! With the `tracing` feature enabled, an `event <level>` clause emits a
+//! `tracing` event for the mismatch, with the fields `expr`, `expected`
+//! (for `if` clauses), `found_variant` (if the value implements `Debug`)
+//! and `location`, as `file:line:column`. It goes before an `else`
+//! clause, or on its own to emit an `error` event right before panicking:
+//!
+//! ```
+//! # use try_utils::*;
+//! # #[derive(Debug)]
+//! # enum Fruit { Apple(i32), Orange(i16) }
+//! # impl Fruit { fn weight(&self) -> i32 { 1 } }
+//! # #[cfg(feature = "tracing")]
+//! # fn main() {
+//! # let z = Fruit::Apple(3);
+//! let n = inner!(z, if Fruit::Apple, event warn, else 0);
+//! # let z = Fruit::Orange(2);
+//! let n = inner!(z, if Fruit::Apple, event warn, else |e| e.weight());
+//! # let z = Fruit::Apple(n);
+//! let n = inner!(z, if Fruit::Apple, event);
+//! # assert_eq!(n, 1);
+//! # }
+//! # #[cfg(not(feature = "tracing"))]
+//! # fn main() {}
+//! ```
+//!
+//! # Error context with `anyhow`
+//! With the `anyhow` feature enabled, a `context` clause can be used in place
+//! of `else`. On mismatch, `inner!` returns early from the surrounding function
+//! with an `anyhow::Error` that includes your formatted context, the expected
+//! variant, the stringified expression and, if the value implements `Debug`,
+//! what was found instead:
+//!
+//! ```
+//! # use try_utils::*;
+//! # #[derive(Debug)]
+//! # struct Session;
+//! # #[derive(Debug)]
+//! # enum Resp { Ok(Session), Busy(u8) }
+//! # #[cfg(feature = "anyhow")]
+//! fn handshake(resp: Resp, peer: &str) -> anyhow::Result<Session> {
+//!     let session = inner!(resp, if Resp::Ok, context "handshake with {}", peer);
+//!     // handshake with 10.0.0.1: expected 'Resp::Ok' inside 'resp', found Busy(3)
+//!     Ok(session)
+//! }
+//! # #[cfg(feature = "anyhow")]
+//! # fn main() {
+//! # let e = handshake(Resp::Busy(3), "10.0.0.1").unwrap_err();
+//! # assert_eq!(e.to_string(), "handshake with 10.0.0.1: expected 'Resp::Ok' inside 'resp', found Busy(3)");
+//! # }
+//! # #[cfg(not(feature = "anyhow"))]
+//! # fn main() {}
+//! ```
+//!
+//! `ok!` accepts the same clause, returning a `Result<T, anyhow::Error>`
+//! instead of returning early.
+//!
+//! # Constant `IntoResult` on nightly
+//! The unstable `nightly` feature makes `IntoResult` a `const` trait, with
+//! constant implementations for `Option` and `Result`, so that the forms
+//! without an `if` clause work in `const fn` as well; the panicking form
+//! is `inner!(const x)`. It needs a nightly compiler that still accepts
+//! the `const_trait_impl` syntax it was written against; expect it to
+//! break with newer nightlies.
+//!
+//! ```
+//! # #![cfg_attr(feature = "nightly", feature(const_trait_impl))]
+//! # use try_utils::*;
+//! # #[cfg(feature = "nightly")]
+//! const fn port(configured: Option<u16>) -> u16 {
+//!     inner!(const configured)
+//! }
+//! # #[cfg(feature = "nightly")]
+//! # fn main() {
+//! # const PORT: u16 = port(Some(8443));
+//! # assert_eq!(PORT, 8443);
+//! # }
+//! # #[cfg(not(feature = "nightly"))]
+//! # fn main() {}
+//! ```
+//!
+//! # Running commands
+//! With the `process` feature enabled, a successful `ExitStatus` converts
+//! into `Ok(())` and any other into `Err` with the status, whose `code()`
+//! is the exit code if there is one. The `Output` of a command converts
+//! too: into its stdout on success, and otherwise into `Err` with the
+//! whole `Output`, stderr included:
+//!
+//! ```no_run
+//! # use try_utils::*;
+//! # #[cfg(feature = "process")]
+//! # fn check() -> Result<Vec<u8>, i32> {
+//! # use std::process::Command;
+//! let out = Command::new("make").arg("check").output().map_err(|_| 1)?;
+//! let stdout = inner!(out, else |o| {
+//!     eprintln!("make failed with {}:", o.status);
+//!     eprintln!("{}", String::from_utf8_lossy(&o.stderr));
+//!     return Err(o.status.code().unwrap_or(1));
+//! });
+//! # Ok(stdout)
+//! # }
+//! # fn main() {}
+//! ```
+//!
+//! # Streams with `futures`
+//! With the `futures` feature enabled, `stream_filter_inner!` filters a
+//! `Stream` down to the payloads of one variant, and `StreamInnerExt` adds
+//! `filter_inner` and `map_inner` to every `Stream` whose items implement
+//! `IntoResult`. The feature does not need `std`.
+//!
+//! # Parallel iterators with `rayon`
+//! With the `rayon` feature enabled, `ParallelInnerExt` adds `par_oks`,
+//! `par_errs` and `par_partition_variants` to every `ParallelIterator`
+//! whose items implement `IntoResult`, and `par_collect_inner!` is the
+//! parallel `collect_inner!`. Both keep the order of the items.
+//!
+//! # `no_std`
+//! Without the default `std` feature, the crate only needs `core`. The
+//! `alloc` feature (implied by `std`) adds what allocates: the macros that
+//! collect into a `Vec`, and the `or_fmt` clause of `ok!`. With its `as`
+//! and `try_push` clauses, `collect_inner!` collects into a collection of
+//! your choice instead, such as a `heapless::Vec`. The `anyhow`, `process`,
+//! `rayon`, `serde_json`, `tokio` and `tracing` integrations turn `std` on.
+//!
+//! # License
+//! Apache2.0/MIT
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "nightly", feature(const_trait_impl, const_destruct))]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+// The const trait syntax does not even parse on stable, so the nightly
+// definition lives in a module that is only loaded with the feature.
+#[cfg(feature = "nightly")]
+mod nightly;
+#[cfg(feature = "nightly")]
+pub use nightly::IntoResult;
+
+/// Converts a value into a Result.
+/// You can implement this for your own types if you want
+/// to use the `inner!` macro in more ergonomic ways.
+///
+/// Functions returning `impl IntoResult<T, E>` work with `inner!` as well,
+/// as long as the bound names both `T` and `E`.
+#[cfg(not(feature = "nightly"))]
+pub trait IntoResult<T, E> {
+    fn into_result(self) -> Result<T, E>;
+}
+
+#[cfg(not(feature = "nightly"))]
+impl<T, E> IntoResult<T, E> for Result<T, E> {
+    #[inline]
+    fn into_result(self) -> Result<T, E> {
+        self
+    }
+}
+
+#[cfg(not(feature = "nightly"))]
+impl<T> IntoResult<T, ()> for Option<T> {
+    #[inline]
+    fn into_result(self) -> Result<T, ()> {
+        self.ok_or(())
+    }
+}
+
+/// Borrows the payload, so that `inner!(&x)` gives a reference into `x`.
+impl<'a, T, E> IntoResult<&'a T, &'a E> for &'a Result<T, E> {
+    #[inline]
+    fn into_result(self) -> Result<&'a T, &'a E> {
+        self.as_ref()
+    }
+}
+
+impl<'a, T, E> IntoResult<&'a mut T, &'a mut E> for &'a mut Result<T, E> {
+    #[inline]
+    fn into_result(self) -> Result<&'a mut T, &'a mut E> {
+        self.as_mut()
+    }
+}
+
+impl<'a, T> IntoResult<&'a T, ()> for &'a Option<T> {
+    #[inline]
+    fn into_result(self) -> Result<&'a T, ()> {
+        self.as_ref().ok_or(())
+    }
+}
+
+impl<'a, T> IntoResult<&'a mut T, ()> for &'a mut Option<T> {
+    #[inline]
+    fn into_result(self) -> Result<&'a mut T, ()> {
+        self.as_mut().ok_or(())
+    }
+}
+
+/// Looks through a `Box`, so that `inner!(boxed)` moves the payload out of
+/// a `Box<Option<T>>` or a `Box<Result<T, E>>` without writing `*boxed`.
+/// Requires the `alloc` feature.
+///
+/// This is only for `Box`: a blanket implementation for everything that
+/// dereferences would overlap with the ones for `Option` and `Result`, and
+/// the other smart pointers cannot be moved out of anyway.
+#[cfg(feature = "alloc")]
+impl<T, E, D: IntoResult<T, E>> IntoResult<T, E> for alloc::boxed::Box<D> {
+    #[inline]
+    fn into_result(self) -> Result<T, E> {
+        (*self).into_result()
+    }
+}
+
+/// A successful exit status is `Ok(())`, any other is `Err` with the
+/// status, with the `process` feature. The error is the status rather than
+/// its code, since a process killed by a signal has no code.
+///
+/// ```no_run
+/// # use try_utils::*;
+/// # #[cfg(feature = "process")]
+/// # fn build() -> std::io::Result<()> {
+/// # use std::process::Command;
+/// let status = Command::new("make").status()?;
+/// inner!(status, else |s| panic!("make failed with {}", s));
+/// # Ok(())
+/// # }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "process")]
+impl IntoResult<(), std::process::ExitStatus> for std::process::ExitStatus {
+    #[inline]
+    fn into_result(self) -> Result<(), std::process::ExitStatus> {
+        if self.success() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// The stdout of a command that exited successfully is `Ok`, with the
+/// `process` feature. Otherwise, the whole `Output` is `Err`, so that the
+/// `else` clause can report the status and stderr.
+///
+/// ```no_run
+/// # use try_utils::*;
+/// # #[cfg(feature = "process")]
+/// # fn head() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+/// # use std::process::Command;
+/// let stdout = inner!(Command::new("git").arg("rev-parse").arg("HEAD").output()?, else |o| {
+///     return Err(String::from_utf8_lossy(&o.stderr).into());
+/// });
+/// # Ok(stdout)
+/// # }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "process")]
+impl IntoResult<Vec<u8>, std::process::Output> for std::process::Output {
+    #[inline]
+    fn into_result(self) -> Result<Vec<u8>, std::process::Output> {
+        if self.status.success() {
+            Ok(self.stdout)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// `Left` is `Ok` and `Right` is `Err`, with the `either` feature. Wrap the
+/// value in `RightIsOk` for the opposite orientation.
+#[cfg(feature = "either")]
+impl<L, R> IntoResult<L, R> for either::Either<L, R> {
+    #[inline]
+    fn into_result(self) -> Result<L, R> {
+        match self {
+            either::Either::Left(l) => Ok(l),
+            either::Either::Right(r) => Err(r),
+        }
+    }
+}
+
+/// Treats `Right` as `Ok` and `Left` as `Err`, with the `either` feature.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "either")]
+/// # fn main() {
+/// # use either::Either;
+/// let e: Either<String, i32> = Either::Right(5);
+/// assert_eq!(inner!(RightIsOk(e)), 5);
+/// # }
+/// # #[cfg(not(feature = "either"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "either")]
+pub struct RightIsOk<L, R>(pub either::Either<L, R>);
+
+#[cfg(feature = "either")]
+impl<L, R> IntoResult<R, L> for RightIsOk<L, R> {
+    #[inline]
+    fn into_result(self) -> Result<R, L> {
+        match self.0 {
+            either::Either::Left(l) => Err(l),
+            either::Either::Right(r) => Ok(r),
+        }
+    }
+}
+
+#[cfg(all(feature = "either", feature = "defmt"))]
+impl<L: defmt::Format, R: defmt::Format> defmt::Format for RightIsOk<L, R> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match &self.0 {
+            either::Either::Left(l) => defmt::write!(f, "RightIsOk(Left({}))", l),
+            either::Either::Right(r) => defmt::write!(f, "RightIsOk(Right({}))", r),
+        }
+    }
+}
+
+#[cfg(feature = "either")]
+pub use either::Either;
+
+/// Splits a parallel iterator of values implementing `IntoResult`, with
+/// the `rayon` feature.
+///
+/// The payloads keep the order of the items they came from: `par_oks` and
+/// `par_errs` collect like any other rayon iterator, which keeps the order
+/// when collecting into a `Vec`, and `par_partition_variants` folds each
+/// chunk of items on its own and appends the chunks in their order. What is
+/// not ordered is when each `into_result` runs, so it should not have side
+/// effects that depend on the order.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "rayon")]
+/// # fn main() {
+/// use rayon::prelude::*;
+///
+/// # let lines = ["1", "x", "3"];
+/// let parsed = lines.par_iter().map(|l| l.parse::<u64>());
+/// let (numbers, errors): (Vec<u64>, Vec<_>) = parsed.par_partition_variants();
+/// # assert_eq!((numbers, errors.len()), (vec![1, 3], 1));
+/// # }
+/// # #[cfg(not(feature = "rayon"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "rayon")]
+pub trait ParallelInnerExt: rayon::iter::ParallelIterator {
+    /// The `Ok` payloads, dropping the rest.
+    fn par_oks<T: Send, E>(self) -> impl rayon::iter::ParallelIterator<Item = T>
+    where
+        Self::Item: IntoResult<T, E>,
+    {
+        self.filter_map(|x| x.into_result().ok())
+    }
+
+    /// The `Err` payloads, dropping the rest.
+    fn par_errs<T, E: Send>(self) -> impl rayon::iter::ParallelIterator<Item = E>
+    where
+        Self::Item: IntoResult<T, E>,
+    {
+        self.filter_map(|x| x.into_result().err())
+    }
+
+    /// The `Ok` payloads and the `Err` payloads, in separate `Vec`s.
+    fn par_partition_variants<T: Send, E: Send>(self) -> (Vec<T>, Vec<E>)
+    where
+        Self::Item: IntoResult<T, E>,
+    {
+        let chunks = self.fold(
+            || (Vec::new(), Vec::new()),
+            |(mut oks, mut errs), x| {
+                match x.into_result() {
+                    Ok(t) => oks.push(t),
+                    Err(e) => errs.push(e),
+                }
+                (oks, errs)
+            },
+        );
+        rayon::iter::ParallelIterator::reduce(
+            chunks,
+            || (Vec::new(), Vec::new()),
+            |(mut oks, mut errs), (mut more_oks, mut more_errs)| {
+                oks.append(&mut more_oks);
+                errs.append(&mut more_errs);
+                (oks, errs)
+            },
+        )
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<I: rayon::iter::ParallelIterator> ParallelInnerExt for I {}
+
+/// Descends into the items of a `futures::Stream` whose items implement
+/// `IntoResult`, with the `futures` feature.
+///
+/// The adapters are `Unpin` if the stream is. For a stream of your own
+/// enum, `stream_filter_inner!` names the variant instead.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "futures")]
+/// # async fn run() {
+/// use futures::stream::{self, StreamExt};
+///
+/// let parsed = stream::iter(["1", "x", "3"]).map(str::parse::<i32>);
+/// let numbers: Vec<i32> = parsed.filter_inner().collect().await;
+/// # assert_eq!(numbers, [1, 3]);
+/// # }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "futures")]
+pub trait StreamInnerExt: futures::stream::Stream + Sized {
+    /// The `Ok` payloads, dropping the other items.
+    fn filter_inner<T, E>(self) -> impl futures::stream::Stream<Item = T>
+    where
+        Self::Item: IntoResult<T, E>,
+    {
+        futures::stream::StreamExt::filter_map(self, |x| core::future::ready(x.into_result().ok()))
+    }
+
+    /// The `Ok` payloads, with the other items turned into payloads by `f`,
+    /// like `inner!(x, else |e| f(e))` for each item.
+    fn map_inner<T, E, F: FnMut(E) -> T>(self, mut f: F) -> impl futures::stream::Stream<Item = T>
+    where
+        Self::Item: IntoResult<T, E>,
+    {
+        futures::stream::StreamExt::map(self, move |x| match x.into_result() {
+            Ok(t) => t,
+            Err(e) => f(e),
+        })
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<S: futures::stream::Stream> StreamInnerExt for S {}
+
+/// Derives `IntoResult<T, Self>` for an enum, with the `derive` feature.
+///
+/// Variants marked `#[ok]` become `Ok`, everything else becomes `Err` with
+/// the whole value. Add `#[ok(into)]` to convert the payload with `Into::into`,
+/// which lets variants with different payload types share a common `T`.
+/// `T` is taken from the first `#[ok]` variant, unless set explicitly with
+/// `#[into_result(ok = Type)]` on the enum.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "derive")]
+/// # fn main() {
+/// #[derive(IntoResult)]
+/// enum Fruit {
+///     #[ok(into)]
+///     Apple(i32),
+///     #[ok(into)]
+///     Orange(i16),
+///     Rotten,
+/// }
+///
+/// assert_eq!(inner!(Fruit::Orange(9)), 9i32);
+/// # let _ = (Fruit::Apple(0), Fruit::Rotten);
+/// # }
+/// # #[cfg(not(feature = "derive"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "derive")]
+pub use try_utils_derive::IntoResult;
+
+/// Generates an iterator extension trait for one variant, with the
+/// `derive` feature.
+///
+/// `impl_inner_ext!(Fruit, Apple, i32)` defines a trait `FruitAppleExt`
+/// for iterators of `Fruit`, whose `apples()` method gives the payload of
+/// every `Fruit::Apple`, skipping the other variants. The method is the
+/// snake case variant name with an `s` appended, and the trait can be made
+/// public with `impl_inner_ext!(pub Fruit, Apple, i32)`.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "derive")]
+/// # fn main() {
+/// # enum Fruit { Apple(i32), Orange(i16) }
+/// impl_inner_ext!(Fruit, Apple, i32);
+///
+/// let fruits = vec![Fruit::Apple(1), Fruit::Orange(2), Fruit::Apple(3)];
+/// assert_eq!(fruits.into_iter().apples().collect::<Vec<_>>(), [1, 3]);
+/// # }
+/// # #[cfg(not(feature = "derive"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "derive")]
+pub use try_utils_derive::impl_inner_ext;
+
+/// The longest stringified expression, in characters, that is put in a
+/// panic message or other diagnostic. Longer expressions are cut, ending
+/// in `…`.
+pub const MAX_EXPR_LEN: usize = 80;
+
+/// What a panicking form of `inner!` knows about the value it could not
+/// descend into, passed to the formatter set with `set_panic_formatter`.
+#[cfg(feature = "std")]
+#[non_exhaustive]
+pub struct PanicContext<'a> {
+    /// The stringified expression, as in the default message.
+    pub expr: &'a str,
+    /// The expected variant, for the `if` clause forms.
+    pub expected: Option<&'a str>,
+    /// The type of the expression, from `core::any::type_name`.
+    pub type_name: &'a str,
+    /// What was found instead, if it implements `Debug`.
+    pub found: Option<&'a dyn core::fmt::Debug>,
+    /// Where the macro was invoked.
+    pub location: &'a core::panic::Location<'a>,
+}
+
+/// Formats the panic message of the panicking forms of `inner!`.
+#[cfg(feature = "std")]
+pub type PanicFormatter = fn(&PanicContext<'_>) -> String;
+
+#[cfg(feature = "std")]
+static PANIC_FORMATTER: std::sync::OnceLock<PanicFormatter> = std::sync::OnceLock::new();
+
+/// Replaces the message that the panicking forms of `inner!` panic with,
+/// e g to match how the rest of a program reports broken invariants.
+///
+/// The formatter can only be set once; later calls give it back as an
+/// error. It is only consulted once a panic is underway. Requires the
+/// `std` feature (enabled by default), and has no effect on
+/// `inner!(const ..)` or with `defmt`, whose messages are not formatted at
+/// runtime.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// set_panic_formatter(|cx| {
+///     format!(r#"{{"incident":"extract","expr":"{}","at":"{}"}}"#, cx.expr, cx.location)
+/// })
+/// .unwrap();
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub fn set_panic_formatter(format: PanicFormatter) -> Result<(), PanicFormatter> {
+    PANIC_FORMATTER.set(format)
+}
+
+/// What `ok!(x, if V, mismatch)` gives on the error side: the value that
+/// was found, along with the expression, the expected variant and where
+/// the macro was invoked.
+///
+/// With the `serde` feature, it implements `Serialize` if what was found
+/// does, to report the failure as structured data. The fields are
+/// serialized under their names here, which are kept stable, and the
+/// location as `file:line:column`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Debug)]
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let e = ok!(Fruit::Orange(3), if Fruit::Apple, mismatch).unwrap_err();
+/// assert_eq!((e.expr, e.expected), ("Fruit::Orange(3)", "Fruit::Apple"));
+/// assert_eq!(e.to_string(), "Expected 'Fruit::Apple' inside 'Fruit::Orange(3)', found Orange(3)");
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Mismatch<T> {
+    /// The stringified expression.
+    pub expr: &'static str,
+    /// The expected variant.
+    pub expected: &'static str,
+    /// What was found instead.
+    pub found: T,
+    /// Where the macro was invoked.
+    pub location: &'static core::panic::Location<'static>,
+}
+
+impl<T: core::fmt::Debug> core::fmt::Display for Mismatch<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Expected '{}' inside '{}', found {:?}",
+            self.expected, self.expr, self.found
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: core::fmt::Debug> std::error::Error for Mismatch<T> {}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for Mismatch<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        struct Location(&'static core::panic::Location<'static>);
+
+        impl serde::Serialize for Location {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self.0)
+            }
+        }
+
+        let mut s = serializer.serialize_struct("Mismatch", 4)?;
+        s.serialize_field("expr", self.expr)?;
+        s.serialize_field("expected", self.expected)?;
+        s.serialize_field("found", &self.found)?;
+        s.serialize_field("location", &Location(self.location))?;
+        s.end()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format> defmt::Format for Mismatch<T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(
+            f,
+            "Expected '{=str}' inside '{=str}', found {} at {=str}:{=u32}:{=u32}",
+            self.expected,
+            self.expr,
+            self.found,
+            self.location.file(),
+            self.location.line(),
+            self.location.column()
+        )
+    }
+}
+
+/// Marks the result of a fallible constructor, such as
+/// `TcpStream::connect` or `File::open`, for use with `inner!`.
+///
+/// `Result` already implements `IntoResult`, so this only serves to
+/// make the intent explicit at the call site, and as an example of
+/// how to wrap another type to give it an `IntoResult` implementation.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::net::TcpStream;
+///
+/// let stream = inner!(TryNew(TcpStream::connect("127.0.0.1:1")), else |e| {
+///     println!("Could not connect: {}", e);
+///     return;
+/// });
+/// # drop(stream);
+/// # }
+/// ```
+pub struct TryNew<T, E>(pub Result<T, E>);
+
+impl<T, E> IntoResult<T, E> for TryNew<T, E> {
+    #[inline]
+    fn into_result(self) -> Result<T, E> {
+        self.0
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T: defmt::Format, E: defmt::Format> defmt::Format for TryNew<T, E> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "TryNew({})", self.0)
+    }
+}
+
+/// Converts a value with `TryFrom` for use with `inner!`, handing back
+/// the original, unconverted value if the conversion fails.
+///
+/// The value is cloned for the conversion attempt, since `TryFrom`
+/// consumes it and its error type does not generally carry it back.
+/// The target type can be given with a turbofish or left to inference.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// let big_val: i64 = 1 << 40;
+/// let small = inner!(TryConvert::<i32, i64>::new(big_val), else |v| {
+///     println!("{} does not fit in an i32", v);
+///     return;
+/// });
+/// # let _ = small;
+/// # }
+/// ```
+pub struct TryConvert<T, U> {
+    value: U,
+    target: core::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, U> TryConvert<T, U> {
+    /// Wraps `value` to be converted into `T`.
+    #[inline]
+    pub fn new(value: U) -> Self {
+        TryConvert {
+            value,
+            target: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: core::convert::TryFrom<U>, U: Clone> IntoResult<T, U> for TryConvert<T, U> {
+    #[inline]
+    fn into_result(self) -> Result<T, U> {
+        let value = self.value;
+        T::try_from(value.clone()).map_err(|_| value)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<T, U: defmt::Format> defmt::Format for TryConvert<T, U> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "TryConvert({})", self.value)
+    }
+}
+
+/// A code point for use with `inner!`, which is a `char` unless it is a
+/// surrogate or out of range.
+///
+/// This is `char::from_u32` as an `IntoResult`, which comes in handy when
+/// reading text one code point at a time.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// assert_eq!(inner!(U32ToChar(0x41)), 'A');
+/// assert_eq!(inner!(U32ToChar(0xD800), else '\u{FFFD}'), '\u{FFFD}');
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct U32ToChar(pub u32);
+
+impl IntoResult<char, ()> for U32ToChar {
+    #[inline]
+    fn into_result(self) -> Result<char, ()> {
+        char::from_u32(self.0).ok_or(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for U32ToChar {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "U32ToChar({=u32:#x})", self.0)
+    }
+}
+
+/// An `Option` whose `None` becomes a `Box<dyn Error>`, for use with
+/// `inner!` in functions returning `Result<_, Box<dyn Error>>`.
+///
+/// `Option` itself converts with `()` as the error, which does not
+/// implement `Error`. The boxed error is a `MissingValue`, which only says
+/// that a value was missing; for a more specific message, use the
+/// `context` clause or `ok_or`. Requires the `std` feature (enabled by
+/// default).
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// use std::error::Error;
+///
+/// fn first_char(s: &str) -> Result<char, Box<dyn Error>> {
+///     let c = inner!(RichOption(s.chars().next()), else |e| return Err(e));
+///     Ok(c)
+/// }
+/// # fn main() {
+/// assert_eq!(first_char("apple").unwrap(), 'a');
+/// assert!(first_char("").is_err());
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RichOption<T>(pub Option<T>);
+
+#[cfg(feature = "std")]
+impl<T> IntoResult<T, Box<dyn std::error::Error>> for RichOption<T> {
+    #[inline]
+    fn into_result(self) -> Result<T, Box<dyn std::error::Error>> {
+        self.0.ok_or_else(|| MissingValue.into())
+    }
+}
+
+#[cfg(all(feature = "std", feature = "defmt"))]
+impl<T: defmt::Format> defmt::Format for RichOption<T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "RichOption({})", self.0)
+    }
+}
+
+/// The error a `RichOption` of `None` converts to.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingValue;
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for MissingValue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("expected a value, found None")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MissingValue {}
+
+/// Serialized like a `Mismatch`, as far as there is anything to say.
+#[cfg(all(feature = "std", feature = "serde"))]
+impl serde::Serialize for MissingValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("MissingValue", 2)?;
+        s.serialize_field("expected", "Some")?;
+        s.serialize_field("found", "None")?;
+        s.end()
+    }
+}
+
+#[cfg(all(feature = "std", feature = "defmt"))]
+impl defmt::Format for MissingValue {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "expected a value, found None")
+    }
+}
+
+/// The item that did not fit with `collect_inner!(.., try_push dst, into
+/// rejected)`, which stops there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow<P, T> {
+    /// The payload of a matching item, for the destination.
+    Payload(P),
+    /// An item of another variant, for the rejected items.
+    Rejected(T),
+}
+
+#[cfg(feature = "defmt")]
+impl<P: defmt::Format, T: defmt::Format> defmt::Format for Overflow<P, T> {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        match self {
+            Overflow::Payload(p) => defmt::write!(f, "Payload({})", p),
+            Overflow::Rejected(t) => defmt::write!(f, "Rejected({})", t),
+        }
+    }
+}
+
+/// A `MaybeUninit` that remembers whether it was initialized, so that
+/// `inner!` can take the value out if it was.
+///
+/// This is an example of `IntoResult` for a wrapper with a safety
+/// invariant: the flag is only set once the value is written, and the
+/// fields are private so that nothing else can set it. That is what makes
+/// the `assume_init` calls inside sound. An initialized value is dropped
+/// with the wrapper, unless it was taken out.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// let mut slot = SafeMaybeUninit::uninit();
+/// if std::env::args().count() > 0 {
+///     slot.write("ready".to_string());
+/// }
+/// let s = inner!(slot, else |_e| return);
+/// assert_eq!(s, "ready");
+/// # }
+/// ```
+pub struct SafeMaybeUninit<T>(core::mem::MaybeUninit<T>, bool);
+
+impl<T> SafeMaybeUninit<T> {
+    /// A slot without a value.
+    #[inline]
+    pub const fn uninit() -> Self {
+        SafeMaybeUninit(core::mem::MaybeUninit::uninit(), false)
+    }
+
+    /// A slot holding `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        SafeMaybeUninit(core::mem::MaybeUninit::new(value), true)
+    }
+
+    /// Whether the slot holds a value.
+    #[inline]
+    pub fn is_init(&self) -> bool {
+        self.1
+    }
+
+    /// Puts `value` in the slot, dropping any previous value.
+    pub fn write(&mut self, value: T) -> &mut T {
+        if self.1 {
+            // SAFETY: the flag is only set after a value was written.
+            unsafe { self.0.assume_init_drop() };
+        }
+        let value = self.0.write(value);
+        self.1 = true;
+        value
+    }
+}
+
+impl<T> Drop for SafeMaybeUninit<T> {
+    fn drop(&mut self) {
+        if self.1 {
+            // SAFETY: the flag is only set after a value was written.
+            unsafe { self.0.assume_init_drop() };
+        }
+    }
+}
+
+impl<T> IntoResult<T, ()> for SafeMaybeUninit<T> {
+    #[inline]
+    fn into_result(self) -> Result<T, ()> {
+        // The value is moved out below, so it must not be dropped again.
+        let this = core::mem::ManuallyDrop::new(self);
+        if this.1 {
+            // SAFETY: the flag is only set after a value was written, and
+            // `this` is never used or dropped afterwards.
+            Ok(unsafe { this.0.assume_init_read() })
+        } else {
+            Err(())
+        }
+    }
+}
+
+/// The `try!` macro - see module level documentation for details.
+#[macro_export]
+macro_rules! inner {
+    // `const` asks for a constant panic, which can be used in `const fn`.
+    // Like `if let`, it has to come before the arms starting with
+    // `$x:expr`, which would fail on the `const` rather than fall through.
+    (const $x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => q,
+            _ => $crate::__inner_const_unexpected!($x, @named stringify!($i)),
+        }
+    }};
+
+    (const $x:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(_) => $crate::__inner_const_unexpected!($x),
+        }
+    }};
+
+    // `if let` reads like the statement it replaces: the value is matched
+    // against the pattern, and the expression after `=>` is evaluated with
+    // its bindings in scope.
+    (if let $p:pat = $e:expr => $x:expr, else |$n:pat| $b:expr) => {{
+        match $e {
+            $p => $x,
+            $n => $b,
+        }
+    }};
+
+    (if let $p:pat = $e:expr => $x:expr, else $b:expr) => {{
+        match $e {
+            $p => $x,
+            _ => $b,
+        }
+    }};
+
+    (if let $p:pat = $e:expr => $x:expr) => {{
+        match $e {
+            $p => $x,
+            ref n => $crate::__inner_unexpected!("inner!", $e, n, $crate::__private::type_of(n), @named stringify!($p)),
+        }
+    }};
+
+    // After the value, `if let` stands for `if` with a destructuring
+    // pattern, and gives back the bindings the same way. The value is
+    // named again after `=`, as in the statement it replaces, which is
+    // checked to be the same expression.
+    ($x:expr, if let $i:ident $(:: $j:ident)* ($($p:tt)+) = $e:expr, else |$n:pat| $b:expr) => {{
+        $crate::__inner_same_expr!($x, $e);
+        $crate::inner!($x, if $i $(:: $j)* ($($p)+), else |$n| $b)
+    }};
+
+    ($x:expr, if let $i:ident $(:: $j:ident)* ($($p:tt)+) = $e:expr, else $b:expr) => {{
+        $crate::__inner_same_expr!($x, $e);
+        $crate::inner!($x, if $i $(:: $j)* ($($p)+), else $b)
+    }};
+
+    ($x:expr, if let $i:ident $(:: $j:ident)* ($($p:tt)+) = $e:expr) => {{
+        $crate::__inner_same_expr!($x, $e);
+        $crate::inner!($x, if $i $(:: $j)* ($($p)+))
+    }};
+
+    // Any other pattern needs an output to be named, and `$i:path` below
+    // would fail on `let` before the arms that explain the grammar.
+    ($x:expr, if let $($t:tt)*) => {
+        compile_error!(concat!(
+            "after the value, `if let` takes a variant with bindings, e g `inner!(x, if let Some(v) = x, else 0)`; ",
+            "for other patterns, put `if let` first and name the output after `=>`, ",
+            "e g `inner!(if let Some(v) = x => v + 1, else 0)`"
+        ))
+    };
+
+    // The destructuring forms come first, since `$i:path` would otherwise
+    // take `E::Pair((a, s))` for a path with parenthesized arguments. The
+    // pattern is given back as an expression, which rebuilds the tuple of
+    // its bindings, so `__inner_bindings!` rejects anything else up front.
+    // That uses `_`-prefixed bindings too, which is fine here.
+    // A trailing `..` skips the remaining fields, so the bindings before it
+    // are all there is to give back.
+    ($x:expr, if $i:ident $(:: $j:ident)* ($($q:ident),+ , ..), else |$e:pat| $b:expr) => {{
+        match $x {
+            $i $(:: $j)* ($($q),+ , ..) => ($($q),+),
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:ident $(:: $j:ident)* ($($q:ident),+ , ..), else $b:expr) => {{
+        match $x {
+            $i $(:: $j)* ($($q),+ , ..) => ($($q),+),
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:ident $(:: $j:ident)* ($($q:ident),+ , ..)) => {{
+        match $x {
+            $i $(:: $j)* ($($q),+ , ..) => ($($q),+),
+            ref n => $crate::__inner_unexpected!(
+                "inner!",
+                $x,
+                n,
+                $crate::__private::type_of(n),
+                @named concat!(stringify!($i) $(, "::", stringify!($j))*)
+            ),
+        }
+    }};
+
+    ($x:expr, if $i:ident $(:: $j:ident)* ($($p:tt)+), else |$e:pat| $b:expr) => {{
+        $crate::__inner_bindings!(@elem {
+            match $x {
+                #[allow(clippy::used_underscore_binding)]
+                $i $(:: $j)* ($($p)+) => ($($p)+),
+                $e => $b,
+            }
+        } [$($p)+])
+    }};
+
+    ($x:expr, if $i:ident $(:: $j:ident)* ($($p:tt)+), else $b:expr) => {{
+        $crate::__inner_bindings!(@elem {
+            match $x {
+                #[allow(clippy::used_underscore_binding)]
+                $i $(:: $j)* ($($p)+) => ($($p)+),
+                _ => $b,
+            }
+        } [$($p)+])
+    }};
+
+    ($x:expr, if $i:ident $(:: $j:ident)* ($($p:tt)+)) => {{
+        $crate::__inner_bindings!(@elem {
+            match $x {
+                #[allow(clippy::used_underscore_binding)]
+                $i $(:: $j)* ($($p)+) => ($($p)+),
+                ref n => $crate::__inner_unexpected!(
+                    "inner!",
+                    $x,
+                    n,
+                    $crate::__private::type_of(n),
+                    @named concat!(stringify!($i) $(, "::", stringify!($j))*)
+                ),
+            }
+        } [$($p)+])
+    }};
+
+    ($x:expr, if $i:path, ref $(. $f:tt)+, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => &q $(. $f)+,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, ref $(. $f:tt)+, else $b:expr) => {{
+        match $x {
+            $i(q) => &q $(. $f)+,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, ref $(. $f:tt)+) => {{
+        match $x {
+            $i(q) => &q $(. $f)+,
+            ref n => $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), $i),
+        }
+    }};
+
+    ($x:expr, if $i:path, $(. $f:tt)+, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => q $(. $f)+,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, $(. $f:tt)+, else $b:expr) => {{
+        match $x {
+            $i(q) => q $(. $f)+,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, $(. $f:tt)+) => {{
+        match $x {
+            $i(q) => q $(. $f)+,
+            ref n => $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), $i),
+        }
+    }};
+
+    // The payload is only visible to the guard through a closure-like
+    // binding; a `q` written by the caller would not name the macro's `q`.
+    ($x:expr, if $i:path, guard |$q:ident| $g:expr, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i($q) if $g => $q,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, guard |$q:ident| $g:expr, else $b:expr) => {{
+        match $x {
+            $i($q) if $g => $q,
+            _ => $b,
+        }
+    }};
+
+    // A payload the guard rejected is reported as such, so the message
+    // does not blame a variant that was in fact the right one.
+    ($x:expr, if $i:path, guard |$q:ident| $g:expr) => {{
+        match $x {
+            $i($q) if $g => $q,
+            ref n @ $i(_) => $crate::__inner_unexpected!(
+                "inner!",
+                $x,
+                n,
+                $crate::__private::type_of(n),
+                @named concat!(stringify!($i), " (guard)")
+            ),
+            ref n => $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), $i),
+        }
+    }};
+
+    ($x:expr, filter |$v:ident| $p:expr, else |$e:pat| $b:expr) => {{
+        match $x {
+            ::core::option::Option::Some($v) if $p => $v,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, filter |$v:ident| $p:expr, else $b:expr) => {{
+        match $x {
+            ::core::option::Option::Some($v) if $p => $v,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, filter |$v:ident| $p:expr) => {{
+        match $x {
+            ::core::option::Option::Some($v) if $p => $v,
+            ref n => $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), Some),
+        }
+    }};
+
+    ($x:expr, err_with $f:expr, else |$e:pat| $b:expr) => {{
+        match $x {
+            ::core::option::Option::Some(q) => q,
+            ::core::option::Option::None => {
+                let $e = ($f)();
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, else log $lvl:ident, $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            ref n => {
+                $crate::__inner_log!($lvl, n, ::core::option::Option::Some(stringify!($i)), $crate::__inner_expr!($x));
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, else log $lvl:ident, $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(ref e) => {
+                $crate::__inner_log!($lvl, e, ::core::option::Option::None, $crate::__inner_expr!($x));
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, then |$v:ident| $t:expr, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i($v) => $t,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, then |$v:ident| $t:expr, else $b:expr) => {{
+        match $x {
+            $i($v) => $t,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, then |$v:ident| $t:expr) => {{
+        match $x {
+            $i($v) => $t,
+            ref n => $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), $i),
+        }
+    }};
+
+    ($x:expr, then |$v:ident| $t:expr, else |$e:pat| $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok($v) => $t,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, then |$v:ident| $t:expr, else $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok($v) => $t,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, then |$v:ident| $t:expr) => {{
+        match $crate::inner!($x) {
+            $v => $t,
+        }
+    }};
+
+    ($x:expr, if $i:path, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, else |$e:pat| $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, else $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, event $lvl:ident, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            n => {
+                $crate::__inner_event!($lvl, &n, ::core::option::Option::Some(stringify!($i)), $crate::__inner_expr!($x));
+                let $e = n;
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, event $lvl:ident, else $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            ref n => {
+                $crate::__inner_event!($lvl, n, ::core::option::Option::Some(stringify!($i)), $crate::__inner_expr!($x));
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, event) => {{
+        match $x {
+            $i(q) => q,
+            ref n => {
+                $crate::__inner_event!(error, n, ::core::option::Option::Some(stringify!($i)), $crate::__inner_expr!($x));
+                $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), $i)
+            }
+        }
+    }};
+
+    ($x:expr, event $lvl:ident, else |$e:pat| $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(e) => {
+                $crate::__inner_event!($lvl, &e, ::core::option::Option::None, $crate::__inner_expr!($x));
+                let $e = e;
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, event $lvl:ident, else $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(ref e) => {
+                $crate::__inner_event!($lvl, e, ::core::option::Option::None, $crate::__inner_expr!($x));
+                $b
+            }
+        }
+    }};
+
+    ($x:expr, event) => {{
+        // Matching rather than `let` keeps temporaries in `$x` alive.
+        match $x {
+            x => {
+                let ty = $crate::__private::type_of(&x);
+                match $crate::IntoResult::into_result(x) {
+                    ::core::result::Result::Ok(q) => q,
+                    ::core::result::Result::Err(ref e) => {
+                        $crate::__inner_event!(error, e, ::core::option::Option::None, $crate::__inner_expr!($x));
+                        $crate::__inner_unexpected!("inner!", $x, e, ty)
+                    }
+                }
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, context $($c:tt)+) => {{
+        match $x {
+            $i(q) => q,
+            n => {
+                return ::core::result::Result::Err(::core::convert::From::from($crate::__inner_context!(
+                    &n,
+                    ::core::option::Option::Some(stringify!($i)),
+                    $crate::__inner_expr!($x),
+                    $($c)+
+                )))
+            }
+        }
+    }};
+
+    ($x:expr, context $($c:tt)+) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(e) => {
+                return ::core::result::Result::Err(::core::convert::From::from($crate::__inner_context!(
+                    &e,
+                    ::core::option::Option::None,
+                    $crate::__inner_expr!($x),
+                    $($c)+
+                )))
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => q,
+            ref n => $crate::__inner_unexpected!("inner!", $x, n, $crate::__private::type_of(n), $i),
+        }
+    }};
+
+    ($x:expr) => {{
+        // Matching rather than `let` keeps temporaries in `$x` alive.
+        match $x {
+            x => {
+                let ty = $crate::__private::type_of(&x);
+                match $crate::IntoResult::into_result(x) {
+                    ::core::result::Result::Ok(q) => q,
+                    ::core::result::Result::Err(ref e) => $crate::__inner_unexpected!("inner!", $x, e, ty),
+                }
+            }
+        }
+    }};
+
+    // What is left are mistakes. These arms only explain the grammar, so
+    // they must stay below every arm that does something.
+    ($x:expr, else) => {
+        compile_error!("missing body after `else`: write `else <expression>` or `else |e| <expression>`")
+    };
+
+    ($x:expr, else $($t:tt)+) => {
+        compile_error!(concat!(
+            "the `else` clause must come last and takes a single expression, ",
+            "e g `inner!(x, if Fruit::Apple, else 0)` or `inner!(x, else |e| return Err(e))`"
+        ))
+    };
+
+    ($x:expr, or $($t:tt)*) => {
+        compile_error!("`inner!` has no `or` clause: for a fallback, use `else`, e g `inner!(x, else 0)`")
+    };
+
+    // Without `|q|`, a guard such as `x.freshness > 0` would silently read
+    // the `x` around the call rather than the payload.
+    ($x:expr, if $i:path, guard $($t:tt)*) => {
+        compile_error!(concat!(
+            "`guard` takes the payload like a closure argument: ",
+            "`guard |q| <condition>`, e g `inner!(x, if Fruit::Apple, guard |a| a.freshness > 0, else 0)`"
+        ))
+    };
+
+    ($x:expr, if $i:path, $($t:tt)*) => {
+        compile_error!(concat!(
+            "unknown clause after `if ",
+            stringify!($i),
+            "`: expected `else`, `else log`, `event`, `context`, `guard |v|`, ",
+            "`then |v|`, `.field` or `ref .field`"
+        ))
+    };
+
+    ($x:expr, if $($t:tt)*) => {
+        compile_error!(concat!(
+            "`if` must be followed by an enum variant, e g `if Fruit::Apple`, ",
+            "and is separated from the next clause by a comma"
+        ))
+    };
+
+    ($($t:tt)*) => {
+        compile_error!(concat!(
+            "expected `inner!(<expression>)`, optionally followed by `, if <Variant>`, ",
+            "`, filter |v| <condition>`, `, err_with <function>` or `, then |v| <expression>`, ",
+            "and then by `, else <expression>`; see the try_utils documentation"
+        ))
+    };
+}
+
+/// Like the panicking forms of `inner!`, but only checked in builds with
+/// `debug_assertions`. For hot paths that tests cover well.
+///
+/// # Safety
+///
+/// The value **must** be the expected variant (or, without an `if`
+/// clause, convert into `Ok`). With `debug_assertions`, a violation panics
+/// with the same message as `inner!`; without them, it is undefined
+/// behavior, as the other case is compiled to `unreachable_unchecked`.
+/// The macro has to be called inside an `unsafe` block in both kinds of
+/// builds, so that this contract stays visible at the call site.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// let parsed: Result<u8, ()> = Ok(3);
+/// // SAFETY: `parsed` was just built as `Ok`.
+/// let n = unsafe { inner_debug_checked!(parsed) };
+/// assert_eq!(n, 3);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inner_debug_checked {
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => q,
+            ref n => {
+                if cfg!(debug_assertions) {
+                    $crate::__inner_unexpected!(
+                        "inner_debug_checked!",
+                        $x,
+                        n,
+                        $crate::__private::type_of(n),
+                        $i
+                    )
+                } else {
+                    ::core::hint::unreachable_unchecked()
+                }
+            }
+        }
+    }};
+
+    ($x:expr) => {{
+        match $x {
+            x => {
+                let ty = $crate::__private::type_of(&x);
+                match $crate::IntoResult::into_result(x) {
+                    ::core::result::Result::Ok(q) => q,
+                    ::core::result::Result::Err(ref e) => {
+                        if cfg!(debug_assertions) {
+                            $crate::__inner_unexpected!("inner_debug_checked!", $x, e, ty)
+                        } else {
+                            ::core::hint::unreachable_unchecked()
+                        }
+                    }
+                }
+            }
+        }
+    }};
+}
+
+/// Descends into the `Ok` side of anything implementing `IntoResult`, or
+/// returns the `Err` side from the enclosing function, converted with `From`.
+///
+/// This is `?` for types that are not `Result`, such as custom enums with an
+/// `IntoResult` implementation.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// fn parse(x: Result<i32, &str>) -> Result<i32, String> {
+///     let v = inner_try!(x);
+///     Ok(v + 1)
+/// }
+/// # fn main() {
+/// assert_eq!(parse(Ok(1)), Ok(2));
+/// assert_eq!(parse(Err("bad")), Err("bad".to_string()));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inner_try {
+    ($x:expr) => {
+        $crate::inner!($x, else |e| return ::core::result::Result::Err(::core::convert::From::from(e)))
+    };
+}
+
+/// Checks that a value is the given variant, running the `else` clause
+/// otherwise. The payload is discarded, so this is for preconditions
+/// where only the variant matters.
+///
+/// The `else` clause must diverge, e g with `return` or `panic!`; an
+/// expression that evaluates to a value is a type error.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// enum State {
+///     Ready(u32),
+///     Busy(u32),
+/// }
+///
+/// #[derive(Debug, PartialEq)]
+/// struct NotReady;
+///
+/// fn start(state: &State) -> Result<(), NotReady> {
+///     ensure_inner!(state, if State::Ready, else return Err(NotReady));
+///     Ok(())
+/// }
+/// # fn main() {
+/// assert_eq!(start(&State::Ready(1)), Ok(()));
+/// assert_eq!(start(&State::Busy(1)), Err(NotReady));
+/// # }
+/// ```
+///
+/// ```compile_fail
+/// # use try_utils::*;
+/// # fn main() {
+/// let x: Option<i32> = None;
+/// ensure_inner!(x, if Some, else println!("not diverging"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ensure_inner {
+    ($x:expr, if $i:path, else $b:expr) => {
+        match $x {
+            $i(_) => {}
+            _ => {
+                // Only a diverging expression coerces to `Infallible`.
+                #[allow(clippy::diverging_sub_expression)]
+                let _: ::core::convert::Infallible = $b;
+            }
+        }
+    };
+}
+
+/// Descends into two `Option`s at once, returning both payloads as a tuple
+/// if both are `Some`, and running the `else` clause otherwise.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// let width = Some(3);
+/// let height = Some(4);
+/// let (w, h) = inner_zip!(width, height, else return);
+/// assert_eq!(w * h, 12);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inner_zip {
+    ($a:expr, $b:expr, else $e:expr) => {{
+        match ($a, $b) {
+            (::core::option::Option::Some(a), ::core::option::Option::Some(b)) => (a, b),
+            _ => $e,
+        }
+    }};
+}
+
+/// Converts your enum to an Option.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # #[derive(Debug, PartialEq)]
+/// # enum Fruit { Apple(i32), Orange(i32) }
+/// assert_eq!(some!(Fruit::Apple(15), if Fruit::Apple), Some(15));
+/// assert_eq!(some!(Fruit::Orange(5), if Fruit::Apple), None);
+/// ```
+///
+/// A guard can be added by naming the payload, either with the `guard`
+/// clause of `inner!` or inside the variant:
+///
+/// ```
+/// # use try_utils::*;
+/// # #[derive(Debug, PartialEq)]
+/// # enum Fruit { Apple(i32), Orange(i32) }
+/// assert_eq!(some!(Fruit::Apple(15), if Fruit::Apple, guard |n| n > 10), Some(15));
+/// assert_eq!(some!(Fruit::Apple(15), if Fruit::Apple(n) if n > 10), Some(15));
+/// assert_eq!(some!(Fruit::Apple(5), if Fruit::Apple(n) if n > 10), None);
+/// ```
+///
+/// Without an `if` clause, `some!` goes through `IntoResult`, so it can be
+/// used to discard the error of e g a channel's `try_recv`:
+///
+/// ```
+/// # use try_utils::*;
+/// # fn handle(_: i32) {}
+/// # let (tx, rx) = std::sync::mpsc::channel();
+/// # tx.send(1).unwrap();
+/// while let Some(msg) = some!(rx.try_recv()) {
+///     handle(msg);
+/// }
+/// ```
+///
+/// With `else_some`, the fallback is a bare payload that gets wrapped in
+/// `Some`, so the result is never `None`. As with `else`, the body is not
+/// a closure, so `return` and `continue` work in it, and `else_some |e|`
+/// binds the unmatched value:
+///
+/// ```
+/// # use try_utils::*;
+/// # enum Fruit { Apple(i32), Orange(i32) }
+/// # fn weight(f: &Fruit) -> i32 { match f { Fruit::Apple(n) | Fruit::Orange(n) => *n } }
+/// assert_eq!(some!(Fruit::Orange(5), if Fruit::Apple, else_some 0), Some(0));
+/// assert_eq!(some!(Fruit::Orange(5), if Fruit::Apple, else_some |e| weight(&e)), Some(5));
+/// ```
+///
+/// If the variant holds an `Option`, add `flatten` to get that `Option`
+/// back instead of an `Option<Option<T>>`:
+///
+/// ```
+/// # use try_utils::*;
+/// # enum Basket { Fruit(Option<i32>), Empty }
+/// # let _ = Basket::Empty;
+/// assert_eq!(some!(Basket::Fruit(Some(3)), if Basket::Fruit, flatten), Some(3));
+/// assert_eq!(some!(Basket::Fruit(None), if Basket::Fruit, flatten), None);
+/// ```
+#[macro_export]
+macro_rules! some {
+    ($x:expr, if $($p:ident)::+ ($n:ident) if $g:expr) => {{
+        match $x {
+            $($p)::+($n) if $g => ::core::option::Option::Some($n),
+            _ => ::core::option::Option::None,
+        }
+    }};
+
+    ($x:expr, if $i:path, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some(q),
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some(q),
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else_some |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some(q),
+            // A diverging body, like `continue`, never reaches `Some`.
+            #[allow(unreachable_code)]
+            $e => {
+                #[allow(clippy::diverging_sub_expression, unused_variables)]
+                let v = $b;
+                ::core::option::Option::Some(v)
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, else_some $b:expr) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some(q),
+            #[allow(unreachable_code)]
+            _ => {
+                #[allow(clippy::diverging_sub_expression, unused_variables)]
+                let v = $b;
+                ::core::option::Option::Some(v)
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, flatten) => {{
+        match $x {
+            $i(q) => q,
+            _ => ::core::option::Option::None,
+        }
+    }};
+
+    ($x:expr, if $i:path, guard |$q:ident| $g:expr) => {{
+        match $x {
+            $i($q) if $g => ::core::option::Option::Some($q),
+            _ => ::core::option::Option::None,
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some(q),
+            _ => ::core::option::Option::None,
+        }
+    }};
+    ($x:expr) => {{
+        ::core::result::Result::ok($crate::IntoResult::into_result($x))
+    }};
+}
+
+/// Converts your enum to an Result.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # #[derive(Debug, PartialEq)]
+/// # enum Fruit { Apple(i32), Orange(i32) }
+/// assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple), Ok(15));
+/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple), Err(Fruit::Orange(5)));
+///
+/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple, or {75}), Err(75));
+/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple, or_fmt "no apple, {}", 5), Err("no apple, 5".to_string()));
+/// assert_eq!(ok!(Fruit::Orange(5), if Fruit::Apple, else {Err(75)}), Err(75));
+///
+/// assert_eq!(ok!(Fruit::Apple(5), if Fruit::Apple(n) if n > 10), Err(Fruit::Apple(5)));
+/// assert_eq!(ok!(Fruit::Apple(5), if Fruit::Apple, guard |n| n > 10), Err(Fruit::Apple(5)));
+/// ```
+///
+/// The `boxed_err` clause puts the whole value in a `Box` on the error
+/// side, so the `Result` is only as large as the payload (or a pointer).
+/// It is worth it for large enums when the expected variant is the common
+/// case, since the `Result` is then cheaper to move around, at the cost
+/// of an allocation on each mismatch. Requires the `alloc` feature.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[derive(Debug, PartialEq)]
+/// # enum Fruit { Apple(i32), Orange(i32) }
+/// let r: Result<i32, Box<Fruit>> = ok!(Fruit::Orange(5), if Fruit::Apple, boxed_err);
+/// assert_eq!(r, Err(Box::new(Fruit::Orange(5))));
+/// ```
+///
+/// The `mismatch` clause gives a `Mismatch` instead, which keeps the value
+/// along with the expression, the expected variant and the location, and
+/// implements `Serialize` with the `serde` feature.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[derive(Debug, PartialEq)]
+/// # enum Fruit { Apple(i32), Orange(i32) }
+/// let e: Mismatch<Fruit> = ok!(Fruit::Orange(5), if Fruit::Apple, mismatch).unwrap_err();
+/// assert_eq!((e.expected, e.found), ("Fruit::Apple", Fruit::Orange(5)));
+/// ```
+#[macro_export]
+macro_rules! ok {
+    ($x:expr, if $($p:ident)::+ ($n:ident) if $g:expr) => {{
+        match $x {
+            $($p)::+($n) if $g => ::core::result::Result::Ok($n),
+            other => ::core::result::Result::Err(other),
+        }
+    }};
+
+    ($x:expr, if $i:path, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, or |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            $e => ::core::result::Result::Err($b),
+        }
+    }};
+
+    ($x:expr, if $i:path, or $b:expr) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            _ => ::core::result::Result::Err($b),
+        }
+    }};
+
+    ($x:expr, if $i:path, or_fmt $($f:tt)+) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            _ => ::core::result::Result::Err($crate::__inner_format!($($f)+)),
+        }
+    }};
+
+    ($x:expr, if $i:path, boxed_err) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            n => ::core::result::Result::Err($crate::__private::Box::new(n)),
+        }
+    }};
+
+    ($x:expr, if $i:path, guard |$q:ident| $g:expr) => {{
+        match $x {
+            $i($q) if $g => ::core::result::Result::Ok($q),
+            n => ::core::result::Result::Err(n),
+        }
+    }};
+
+    ($x:expr, if $i:path, mismatch) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            n => ::core::result::Result::Err($crate::__private::mismatch(
+                $crate::__inner_expr!($x),
+                stringify!($i),
+                n,
+            )),
+        }
+    }};
+
+    ($x:expr, if $i:path, context $($c:tt)+) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            n => ::core::result::Result::Err($crate::__inner_context!(
+                &n,
+                ::core::option::Option::Some(stringify!($i)),
+                $crate::__inner_expr!($x),
+                $($c)+
+            )),
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => ::core::result::Result::Ok(q),
+            n => ::core::result::Result::Err(n),
+        }
+    }};
+}
+
+/// Like `ok!`, for a variant whose payload is itself a `Result`, or
+/// anything else implementing `IntoResult`, giving that result instead of
+/// nesting it.
+///
+/// Any other variant becomes the error: converted with `Into` into the
+/// payload's error type, or given explicitly with an `or` clause.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Field {
+///     Parsed(Result<u8, String>),
+///     Missing,
+/// }
+///
+/// let field = Field::Parsed(Err("not a number".to_string()));
+/// assert_eq!(ok_flat!(field, if Field::Parsed, or "missing".to_string()), Err("not a number".to_string()));
+/// assert_eq!(ok_flat!(Field::Missing, if Field::Parsed, or "missing".to_string()), Err("missing".to_string()));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ok_flat {
+    ($x:expr, if $i:path, or |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => $crate::IntoResult::into_result(q),
+            $e => ::core::result::Result::Err($b),
+        }
+    }};
+
+    ($x:expr, if $i:path, or $b:expr) => {{
+        match $x {
+            $i(q) => $crate::IntoResult::into_result(q),
+            _ => ::core::result::Result::Err($b),
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => $crate::IntoResult::into_result(q),
+            n => ::core::result::Result::Err(::core::convert::Into::into(n)),
+        }
+    }};
+}
+
+/// Asserts that a value is the given variant, and that its payload equals
+/// an expected value.
+///
+/// The value is only borrowed. On failure, the panic message shows the
+/// expected payload and what was found, so the payload needs to implement
+/// `Debug` and `PartialEq`. The enum itself is rendered too, if it
+/// implements `Debug`. With the `defmt` feature, the payload needs to
+/// implement `defmt::Format` instead of `Debug`, and only the type of a
+/// value of another variant is shown.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Debug)]
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let z = Fruit::Apple(15);
+/// assert_inner_eq!(z, Fruit::Apple, 15);
+/// # let _ = Fruit::Orange(0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_inner_eq {
+    ($x:expr, $i:path, $v:expr) => {{
+        match &$x {
+            $i(q) => {
+                let expected = $v;
+                if !(*q == expected) {
+                    $crate::__inner_assert_failed!(payload $i, $x, expected, q);
+                }
+            }
+            n => $crate::__inner_assert_failed!(variant $i, $x, n),
+        }
+    }};
+}
+
+#[cfg(not(feature = "defmt"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_assert_failed {
+    (payload $i:path, $x:expr, $expected:expr, $found:expr) => {
+        ::core::panic!(
+            "assertion failed: payload of '{}' inside '{}'\n  expected: {:?}\n     found: {:?}",
+            stringify!($i),
+            $crate::__inner_expr!($x),
+            $expected,
+            $found
+        )
+    };
+    (variant $i:path, $x:expr, $found:expr) => {
+        ::core::panic!(
+            "assertion failed: {}",
+            $crate::__private::Message {
+                expr: $crate::__inner_expr!($x),
+                expected: ::core::option::Option::Some(stringify!($i)),
+                found: $crate::__inner_found!($found),
+            }
+        )
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_assert_failed {
+    (payload $i:path, $x:expr, $expected:expr, $found:expr) => {
+        ::defmt::panic!(
+            "assertion failed: payload of '{=str}' inside '{=str}'\n  expected: {}\n     found: {}",
+            stringify!($i),
+            $crate::__inner_expr!($x),
+            $expected,
+            $found
+        )
+    };
+    (variant $i:path, $x:expr, $found:expr) => {{
+        let found = $found;
+        ::defmt::panic!(
+            "assertion failed: expected '{=str}' inside '{=str}' (type {=str})",
+            stringify!($i),
+            $crate::__inner_expr!($x),
+            $crate::__private::type_of(found).name()
+        )
+    }};
+}
+
+/// Descends into every item of an iterator, collecting the payloads of the
+/// matching items into a `Vec`, and pushing the other items into a separate
+/// collection instead of stopping at the first mismatch.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let basket = vec![Fruit::Apple(1), Fruit::Orange(2), Fruit::Apple(3)];
+/// let mut rejected = vec![];
+/// let apples = collect_inner!(basket, if Fruit::Apple, into rejected);
+/// assert_eq!(apples, vec![1, 3]);
+/// assert_eq!(rejected, vec![Fruit::Orange(2)]);
+/// # }
+/// ```
+///
+/// The forms above need the `alloc` feature. Without it, name the
+/// collection to collect into with `as`. It is built with `Default` and
+/// filled with `Extend`, as are the rejected items given to `into`:
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::collections::BTreeSet;
+///
+/// let parsed = ["3", "x", "1", "3"].map(str::parse::<u8>);
+/// let numbers = collect_inner!(parsed, if Ok, as BTreeSet<u8>);
+/// assert_eq!(numbers.into_iter().collect::<Vec<_>>(), [1, 3]);
+/// # }
+/// ```
+///
+/// A `heapless::Vec` cannot grow, and would panic from `Extend` once it
+/// is full. Named after `as`, it is filled with its fallible `push`
+/// instead, and comes back as a `Result`, with the first payload that did
+/// not fit as the error. Rejected items given to `into` are still added
+/// with `Extend`.
+///
+/// To fill collections that already exist, `try_push` calls their `push`,
+/// which has to return a `Result` like that of `heapless::Vec`. The other
+/// items are skipped, or with `into`, pushed into a second collection.
+/// Either way, it stops at the first item that does not fit, and returns
+/// it as `Err`, wrapped in an `Overflow` if there are two collections:
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "heapless")]
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let basket = || [Fruit::Apple(1), Fruit::Orange(2), Fruit::Apple(3), Fruit::Orange(4)];
+/// let apples = collect_inner!(basket(), if Fruit::Apple, as heapless::Vec<i32, 1>);
+/// assert_eq!(apples.unwrap_err(), 3);
+///
+/// let mut apples: heapless::Vec<i32, 2> = heapless::Vec::new();
+/// let mut rejected: heapless::Vec<Fruit, 1> = heapless::Vec::new();
+/// let pushed = collect_inner!(basket(), if Fruit::Apple, try_push apples, into rejected);
+/// assert_eq!(pushed, Err(Overflow::Rejected(Fruit::Orange(4))));
+/// assert_eq!(apples, [1, 3]);
+/// assert_eq!(rejected, [Fruit::Orange(2)]);
+/// # }
+/// # #[cfg(not(feature = "heapless"))]
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! collect_inner {
+    ($iter:expr, if $i:path, into $errs:expr, as $t:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::Extending as _;
+        let errs = &mut $errs;
+        let mut oks: $crate::__private::Collecting<$t> =
+            $crate::__private::Collecting(::core::default::Default::default());
+        let mut full = ::core::option::Option::None;
+        for item in $iter {
+            match item {
+                $i(q) => {
+                    if let ::core::result::Result::Err(q) = oks.push_item(q) {
+                        full = ::core::option::Option::Some(q);
+                        break;
+                    }
+                }
+                other => ::core::iter::Extend::extend(errs, ::core::option::Option::Some(other)),
+            }
+        }
+        oks.finish(full)
+    }};
+
+    ($iter:expr, if $i:path, into $errs:expr) => {{
+        let errs = &mut $errs;
+        let mut oks = $crate::__private::Vec::new();
+        for item in $iter {
+            match item {
+                $i(q) => oks.push(q),
+                other => errs.push(other),
+            }
+        }
+        oks
+    }};
+
+    ($iter:expr, if $i:path, as $t:ty) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::Extending as _;
+        let mut oks: $crate::__private::Collecting<$t> =
+            $crate::__private::Collecting(::core::default::Default::default());
+        let mut full = ::core::option::Option::None;
+        for item in $iter {
+            if let $i(q) = item {
+                if let ::core::result::Result::Err(q) = oks.push_item(q) {
+                    full = ::core::option::Option::Some(q);
+                    break;
+                }
+            }
+        }
+        oks.finish(full)
+    }};
+
+    ($iter:expr, if $i:path, try_push $dst:expr, into $errs:expr) => {{
+        let dst = &mut $dst;
+        let errs = &mut $errs;
+        let mut pushed = ::core::result::Result::Ok(());
+        for item in $iter {
+            let full = match item {
+                $i(q) => dst.push(q).map_err($crate::Overflow::Payload),
+                other => errs.push(other).map_err($crate::Overflow::Rejected),
+            };
+            if full.is_err() {
+                pushed = full;
+                break;
+            }
+        }
+        pushed
+    }};
+
+    ($iter:expr, if $i:path, try_push $dst:expr) => {{
+        let dst = &mut $dst;
+        let mut pushed = ::core::result::Result::Ok(());
+        for item in $iter {
+            if let $i(q) = item {
+                if let ::core::result::Result::Err(q) = dst.push(q) {
+                    pushed = ::core::result::Result::Err(q);
+                    break;
+                }
+            }
+        }
+        pushed
+    }};
+}
+
+/// Like `collect_inner!`, but over a rayon `ParallelIterator`, with the
+/// `rayon` feature.
+///
+/// The matching payloads are collected in the order of their items, and
+/// the other items are appended to the `into` collection in their order,
+/// after what it already held.
+///
+/// ```
+/// # use try_utils::*;
+/// # #[derive(Debug, PartialEq)]
+/// # enum Fruit { Apple(i32), Orange(i16) }
+/// # #[cfg(feature = "rayon")]
+/// # fn main() {
+/// use rayon::prelude::*;
+///
+/// # let basket = vec![Fruit::Apple(1), Fruit::Orange(2), Fruit::Apple(3)];
+/// let mut rejected = vec![];
+/// let apples = par_collect_inner!(basket.into_par_iter(), if Fruit::Apple, into rejected);
+/// # assert_eq!((apples, rejected), (vec![1, 3], vec![Fruit::Orange(2)]));
+/// # }
+/// # #[cfg(not(feature = "rayon"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "rayon")]
+#[macro_export]
+macro_rules! par_collect_inner {
+    ($iter:expr, if $i:path, into $errs:expr) => {{
+        let errs = &mut $errs;
+        let (oks, rest): ($crate::__private::Vec<_>, $crate::__private::Vec<_>) =
+            $crate::__private::rayon::iter::ParallelIterator::partition_map(
+                $iter,
+                |item| match item {
+                    $i(q) => $crate::__private::rayon::iter::Either::Left(q),
+                    other => $crate::__private::rayon::iter::Either::Right(other),
+                },
+            );
+        ::core::iter::Extend::extend(errs, rest);
+        oks
+    }};
+}
+
+/// Collects references to the payloads of every matching item of a slice,
+/// without cloning or consuming it.
+///
+/// The references returned borrow from the slice. Several variants holding
+/// the same payload type can be joined with `|`, and a `guard` clause
+/// filters the payloads by name. `select_variant_mut!` is the twin over
+/// `&mut [T]`, collecting `&mut` references.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Node {
+///     Text(String),
+///     Comment(String),
+///     Break,
+/// }
+///
+/// let nodes = vec![
+///     Node::Text("a".to_string()),
+///     Node::Break,
+///     Node::Comment("b".to_string()),
+///     Node::Text("cc".to_string()),
+/// ];
+///
+/// let text: Vec<&String> = select_variant!(&nodes, if Node::Text);
+/// assert_eq!(text, ["a", "cc"]);
+///
+/// let all = select_variant!(&nodes, if Node::Text | Node::Comment);
+/// assert_eq!(all, ["a", "b", "cc"]);
+///
+/// let long = select_variant!(&nodes, if Node::Text, guard |s| s.len() > 1);
+/// assert_eq!(long, ["cc"]);
+/// # }
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! select_variant {
+    ($s:expr, if $i:path $(| $j:path)*, guard |$q:ident| $g:expr) => {{
+        let s: &[_] = $s;
+        let mut out = $crate::__private::Vec::new();
+        for item in s {
+            match item {
+                $i($q) $(| $j($q))* if $g => out.push($q),
+                _ => {}
+            }
+        }
+        out
+    }};
+
+    ($s:expr, if $i:path $(| $j:path)*) => {{
+        let s: &[_] = $s;
+        let mut out = $crate::__private::Vec::new();
+        for item in s {
+            match item {
+                $i(q) $(| $j(q))* => out.push(q),
+                _ => {}
+            }
+        }
+        out
+    }};
+}
+
+/// Collects mutable references to the payloads of every matching item of
+/// a slice. See `select_variant!` for the accepted forms.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Node {
+///     Text(String),
+///     Break,
+/// }
+///
+/// let mut nodes = [Node::Text("a".to_string()), Node::Break, Node::Text("b".to_string())];
+/// for text in select_variant_mut!(&mut nodes, if Node::Text) {
+///     text.push('!');
+/// }
+/// assert_eq!(select_variant!(&nodes, if Node::Text), ["a!", "b!"]);
+/// # }
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! select_variant_mut {
+    ($s:expr, if $i:path $(| $j:path)*, guard |$q:ident| $g:expr) => {{
+        let s: &mut [_] = $s;
+        let mut out = $crate::__private::Vec::new();
+        for item in s {
+            match item {
+                $i($q) $(| $j($q))* if $g => out.push($q),
+                _ => {}
+            }
+        }
+        out
+    }};
+
+    ($s:expr, if $i:path $(| $j:path)*) => {{
+        let s: &mut [_] = $s;
+        let mut out = $crate::__private::Vec::new();
+        for item in s {
+            match item {
+                $i(q) $(| $j(q))* => out.push(q),
+                _ => {}
+            }
+        }
+        out
+    }};
+}
+
+/// Upgrades a `Weak` reference (from either `Rc` or `Arc`) and descends
+/// into the variant it points to, returning a clone of the payload.
+///
+/// The `else` clause runs both if the value has been dropped and if it is
+/// not the expected variant.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::rc::Rc;
+///
+/// enum Fruit {
+///     Apple(String),
+///     Orange(i16),
+/// }
+///
+/// let fruit = Rc::new(Fruit::Apple("green".to_string()));
+/// let weak = Rc::downgrade(&fruit);
+/// assert_eq!(inner_upgrade!(weak, if Fruit::Apple, else String::new()), "green");
+/// drop(fruit);
+/// assert_eq!(inner_upgrade!(weak, if Fruit::Apple, else String::new()), "");
+/// # let _ = Fruit::Orange(0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inner_upgrade {
+    ($w:expr, if $i:path, else $b:expr) => {{
+        match $w.upgrade().as_deref() {
+            ::core::option::Option::Some($i(q)) => ::core::clone::Clone::clone(q),
+            _ => $b,
+        }
+    }};
+}
+
+/// Descends into a variant and pairs its payload with a "rest" value,
+/// for decoders that continue after a header.
+///
+/// Returns `Some((payload, rest))` if the variant matches and `None`
+/// otherwise. The `rest` expression is only evaluated on a match; without
+/// a `rest` clause, the rest is `()`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Clone, Copy)]
+/// enum Packet {
+///     Header(u16),
+///     Data(u8),
+/// }
+///
+/// let packets = [Packet::Header(2), Packet::Data(7), Packet::Data(9)];
+/// let (len, body) = split_head_inner!(packets[0], if Packet::Header, rest &packets[1..]).unwrap();
+/// assert_eq!(len, 2);
+/// assert_eq!(body.len(), 2);
+/// # let _ = Packet::Data(0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! split_head_inner {
+    ($x:expr, if $i:path, rest $r:expr) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some((q, $r)),
+            _ => ::core::option::Option::None,
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => ::core::option::Option::Some((q, ())),
+            _ => ::core::option::Option::None,
+        }
+    }};
+}
+
+/// Runs a closure on the payload of a variant and tells whether it matched.
+///
+/// This is `matches!` that also does something with what it found, which
+/// reads well in an `if` condition. The closure only runs on a match, and
+/// its return value is discarded. Without an `if` clause, the value goes
+/// through `IntoResult` instead.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let mut eaten = 0;
+/// if inner_test!(Fruit::Apple(3), if Fruit::Apple, |n| eaten += n) {
+///     println!("ate an apple");
+/// }
+/// assert!(!inner_test!(Fruit::Orange(2), if Fruit::Apple, |n| eaten += n));
+/// assert_eq!(eaten, 3);
+/// assert!(inner_test!(Some(1), |n| eaten += n));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inner_test {
+    ($x:expr, if $i:path, $f:expr) => {{
+        match $x {
+            $i(q) => {
+                ($f)(q);
+                true
+            }
+            _ => false,
+        }
+    }};
+
+    ($x:expr, $f:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => {
+                ($f)(q);
+                true
+            }
+            ::core::result::Result::Err(_) => false,
+        }
+    }};
+}
+
+/// Descends into whichever of several variants a value is, converting the
+/// payload into a common type with `Into`.
+///
+/// This is the call site counterpart of an `IntoResult` implementation
+/// with more than one `Ok` variant. Without an `else` clause, it panics
+/// for any other variant, and the message lists every accepted one.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Label {
+///     Text(&'static str),
+///     Glyph(char),
+///     Blank,
+/// }
+///
+/// let label = Label::Glyph('x');
+/// let s = match_any_inner!(label, { Label::Text, Label::Glyph } into String, else String::new());
+/// assert_eq!(s, "x");
+/// # let _ = (Label::Text(""), Label::Blank);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! match_any_inner {
+    ($x:expr, { $($i:path),+ $(,)? } into $t:ty, else |$e:pat| $b:expr) => {{
+        match $x {
+            $($i(q) => ::core::convert::Into::<$t>::into(q),)+
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, { $($i:path),+ $(,)? } into $t:ty, else $b:expr) => {{
+        match $x {
+            $($i(q) => ::core::convert::Into::<$t>::into(q),)+
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, { $i:path $(, $j:path)* $(,)? } into $t:ty) => {{
+        match $x {
+            $i(q) => ::core::convert::Into::<$t>::into(q),
+            $($j(q) => ::core::convert::Into::<$t>::into(q),)*
+            ref n => $crate::__inner_unexpected!(
+                "match_any_inner!",
+                $x,
+                n,
+                $crate::__private::type_of(n),
+                @named concat!(stringify!($i) $(, " | ", stringify!($j))*)
+            ),
+        }
+    }};
+}
+
+/// Takes the payload of the first of several candidate variants that
+/// matches, converting each on its own way to a common type.
+///
+/// A candidate can be followed by `=> f`, with `f` a function or closure
+/// applied to its payload. With `into T` after the list, every result is
+/// then converted with `Into`; without it, they must all have one type.
+/// Unlike `match_any_inner!`, the candidates do not need to hold
+/// convertible payloads, as long as their functions unify them.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Reading {
+///     Celsius(f64),
+///     Fahrenheit(f64),
+///     Raw(u16),
+///     Unknown,
+/// }
+///
+/// let r = Reading::Fahrenheit(212.0);
+/// let c = inner_first!(r, [
+///     Reading::Celsius,
+///     Reading::Fahrenheit => |f| (f - 32.0) / 1.8,
+///     Reading::Raw => f64::from,
+/// ], else f64::NAN);
+/// assert_eq!(c, 100.0);
+///
+/// let small = inner_first!(Reading::Raw(3), [Reading::Raw, Reading::Celsius => |c: f64| c as u16] into u32, else 0);
+/// assert_eq!(small, 3);
+/// # let _ = Reading::Unknown;
+/// # }
+/// ```
+#[macro_export]
+macro_rules! inner_first {
+    ($x:expr, [ $($i:path $(=> $f:expr)?),+ $(,)? ] into $t:ty, else |$e:pat| $b:expr) => {{
+        match $x {
+            $($i(q) => ::core::convert::Into::<$t>::into($crate::inner_first!(@map q $(, $f)?)),)+
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, [ $($i:path $(=> $f:expr)?),+ $(,)? ] into $t:ty, else $b:expr) => {{
+        match $x {
+            $($i(q) => ::core::convert::Into::<$t>::into($crate::inner_first!(@map q $(, $f)?)),)+
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, [ $($i:path $(=> $f:expr)?),+ $(,)? ], else |$e:pat| $b:expr) => {{
+        match $x {
+            $($i(q) => $crate::inner_first!(@map q $(, $f)?),)+
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, [ $($i:path $(=> $f:expr)?),+ $(,)? ], else $b:expr) => {{
+        match $x {
+            $($i(q) => $crate::inner_first!(@map q $(, $f)?),)+
+            _ => $b,
+        }
+    }};
+
+    (@map $q:ident) => {
+        $q
+    };
+
+    (@map $q:ident, $f:expr) => {
+        ($f)($q)
+    };
+}
+
+/// Moves the payload of one variant into another variant, for state
+/// transitions where the payload carries over.
+///
+/// Both variants need to hold the same payload type. If the value is not
+/// the `from` variant, the `else` clause runs (with the value bound if
+/// written as `else |e|`); without one, the value is given back unchanged.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum State {
+///     Running(u32),
+///     Paused(u32),
+///     Stopped,
+/// }
+///
+/// let state = State::Running(7);
+/// let state = swap_variant!(state, State::Running => State::Paused);
+/// assert_eq!(state, State::Paused(7));
+///
+/// let state = swap_variant!(State::Stopped, State::Running => State::Paused);
+/// assert_eq!(state, State::Stopped);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! swap_variant {
+    ($x:expr, $from:path => $to:path, else |$e:pat| $b:expr) => {{
+        match $x {
+            $from(q) => $to(q),
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, $from:path => $to:path, else $b:expr) => {{
+        match $x {
+            $from(q) => $to(q),
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, $from:path => $to:path) => {{
+        match $x {
+            $from(q) => $to(q),
+            n => n,
+        }
+    }};
+}
+
+/// Takes the payload out of a variant behind a `&mut` and puts a new value
+/// in its place, for state machines stored in a field.
+///
+/// On a match, the old payload is returned and the place is set to the new
+/// value. Otherwise, the place is left alone and the new value is not even
+/// evaluated: the `else` clause runs instead, with the `&mut` bound if
+/// written as `else |e|`. Without an `else` clause, it panics like `inner!`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum State {
+///     Running(u32),
+///     Stopped,
+/// }
+///
+/// let mut state = State::Running(7);
+/// assert_eq!(replace_inner!(&mut state, if State::Running, State::Stopped), 7);
+/// assert_eq!(state, State::Stopped);
+///
+/// assert_eq!(replace_inner!(&mut state, if State::Running, State::Stopped, else 0), 0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! replace_inner {
+    ($x:expr, if $i:path, $new:expr, else |$e:pat| $b:expr) => {{
+        let place: &mut _ = $x;
+        if let $i(_) = *place {
+            $crate::replace_inner!(@take place, $i, $new)
+        } else {
+            match place {
+                $e => $b,
+            }
+        }
+    }};
+
+    ($x:expr, if $i:path, $new:expr, else $b:expr) => {{
+        let place: &mut _ = $x;
+        if let $i(_) = *place {
+            $crate::replace_inner!(@take place, $i, $new)
+        } else {
+            $b
+        }
+    }};
+
+    ($x:expr, if $i:path, $new:expr) => {{
+        let place: &mut _ = $x;
+        if let $i(_) = *place {
+            $crate::replace_inner!(@take place, $i, $new)
+        } else {
+            let n = &*place;
+            $crate::__inner_unexpected!("replace_inner!", $x, n, $crate::__private::type_of(n), $i)
+        }
+    }};
+
+    // Only reached once the variant was checked, so the other arm is dead.
+    (@take $place:ident, $i:path, $new:expr) => {
+        match ::core::mem::replace($place, $new) {
+            $i(q) => q,
+            _ => ::core::unreachable!(),
+        }
+    };
+}
+
+/// Converts between a variant holding an `Option` and an `Option` of its
+/// payload.
+///
+/// With `if`, a value of the enum becomes an `Option`: `Some` only if it
+/// is the variant and its payload is `Some`. An `else` clause runs for
+/// any other variant instead, to tell that case apart from an empty
+/// payload; it gets the value with `else |e|`.
+///
+/// With `into`, an `Option` becomes the variant again. `None` becomes the
+/// variant holding `None`, unless an `or` clause gives another value for
+/// it.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// #[derive(Debug, PartialEq)]
+/// enum Response {
+///     Cached(Option<u32>),
+///     Miss,
+/// }
+///
+/// assert_eq!(transpose_variant!(Response::Cached(Some(3)), if Response::Cached), Some(3));
+/// assert_eq!(transpose_variant!(Response::Cached(None), if Response::Cached, else Some(0)), None);
+/// assert_eq!(transpose_variant!(Response::Miss, if Response::Cached, else Some(0)), Some(0));
+///
+/// assert_eq!(transpose_variant!(Some(4), into Response::Cached), Response::Cached(Some(4)));
+/// assert_eq!(transpose_variant!(None, into Response::Cached, or Response::Miss), Response::Miss);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! transpose_variant {
+    ($x:expr, if $i:path, else |$e:pat| $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            $e => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match $x {
+            $i(q) => q,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => q,
+            _ => ::core::option::Option::None,
+        }
+    }};
+
+    ($x:expr, into $i:path, or $b:expr) => {{
+        match $x {
+            ::core::option::Option::Some(q) => $i(::core::option::Option::Some(q)),
+            ::core::option::Option::None => $b,
+        }
+    }};
+
+    ($x:expr, into $i:path) => {{
+        $i($x)
+    }};
+}
+
+/// Handles both sides of a value implementing `IntoResult`, like
+/// `Result::map_or_else` with the arms written in place.
+///
+/// The arms are the bodies of a plain match rather than closures, so
+/// `return`, `break`, `continue` and `?` in them act on the caller. As in
+/// any match, both arms must give the same type.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// fn describe(input: &str) -> Result<String, String> {
+///     let n = fold_result!(input.parse::<i32>(), |n| n, |e| {
+///         if input.is_empty() {
+///             return Ok("nothing".to_string());
+///         }
+///         return Err(format!("{:?}: {}", input, e));
+///     });
+///     Ok(format!("the number {}", n))
+/// }
+/// # fn main() {
+/// assert_eq!(describe("3").unwrap(), "the number 3");
+/// assert_eq!(describe("").unwrap(), "nothing");
+/// assert!(describe("x").is_err());
+/// # }
+/// ```
+#[macro_export]
+macro_rules! fold_result {
+    ($x:expr, |$o:pat| $a:expr, |$e:pat| $b:expr $(,)?) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok($o) => $a,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+}
+
+/// Descends into a variant inside a loop, skipping to the next iteration
+/// if the value is something else.
+///
+/// This is `inner!(x, if V, else continue)`. Without an `if` clause, the
+/// value goes through `IntoResult`. See also `extract_or_break!`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let mut total = 0;
+/// for f in [Fruit::Apple(3), Fruit::Orange(1), Fruit::Apple(4)] {
+///     total += extract_or_continue!(f, if Fruit::Apple);
+/// }
+/// assert_eq!(total, 7);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! extract_or_continue {
+    ($x:expr, if $i:path) => {
+        $crate::inner!($x, if $i, else continue)
+    };
+
+    ($x:expr) => {
+        $crate::inner!($x, else continue)
+    };
+}
+
+/// Descends into a variant inside a loop, leaving the loop at the first
+/// value that is something else.
+///
+/// This is `inner!(x, if V, else break)`. Without an `if` clause, the
+/// value goes through `IntoResult`. See also `extract_or_continue!`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// let mut read = Vec::new();
+/// for n in ["1", "2", "x", "4"] {
+///     read.push(extract_or_break!(n.parse::<i32>()));
+/// }
+/// assert_eq!(read, [1, 2]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! extract_or_break {
+    ($x:expr, if $i:path) => {
+        $crate::inner!($x, if $i, else break)
+    };
+
+    ($x:expr) => {
+        $crate::inner!($x, else break)
+    };
+}
+
+/// Descends into the first item of a collection, running the `else`
+/// clause if it is empty.
+///
+/// The collection is consumed with `IntoIterator`, so for a `Vec` the
+/// first element is moved out and the rest dropped. Put `ref` in front to
+/// only borrow it and get a reference to the first item instead.
+///
+/// Nothing here is specific to `Vec`: any collection implementing
+/// `IntoIterator` by value and by reference works the same. That includes
+/// `smallvec::SmallVec` and `arrayvec::ArrayVec`, which need no feature of
+/// this crate and are not turned into a `Vec` on the way.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// fn first_word(words: Vec<String>) -> String {
+///     first_inner!(words, else return "nothing".to_string())
+/// }
+/// # fn main() {
+/// let words = vec!["hello".to_string(), "world".to_string()];
+/// assert_eq!(first_inner!(ref words, else return), "hello");
+/// assert_eq!(first_word(words), "hello");
+/// assert_eq!(first_word(vec![]), "nothing");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! first_inner {
+    (ref $v:expr, else $($b:tt)+) => {
+        $crate::inner!(
+            ::core::iter::Iterator::next(&mut ::core::iter::IntoIterator::into_iter(&$v)).ok_or(()),
+            else $($b)+
+        )
+    };
+
+    ($v:expr, else $($b:tt)+) => {
+        $crate::inner!(
+            ::core::iter::Iterator::next(&mut ::core::iter::IntoIterator::into_iter($v)).ok_or(()),
+            else $($b)+
+        )
+    };
+}
+
+/// Descends into a variant in the initializer of a `const` or `static`
+/// item, failing the build if it is another variant.
+///
+/// Unlike `inner!`, the match is always evaluated at compile time, in a
+/// `const` block, so a mismatch fails the build and never panics at run
+/// time. The message names the expected variant and the expression, and
+/// shows up in the compile error. A value only known at run time, such as
+/// a local variable, is rejected; use `inner!` for those. The payload is
+/// copied out, so it has to be `Copy` when the value is reached through a
+/// reference, as with an item of a `const` slice.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Entry {
+///     Timeout(u64),
+///     Retries(u32),
+/// }
+///
+/// const DEFAULTS: &[Entry] = &[Entry::Timeout(30), Entry::Retries(3)];
+/// const DEFAULT_TIMEOUT: u64 = const_inner!(DEFAULTS[0], if Entry::Timeout);
+/// static DEFAULT_RETRIES: u32 = const_inner!(DEFAULTS[1], if Entry::Retries);
+/// assert_eq!((DEFAULT_TIMEOUT, DEFAULT_RETRIES), (30, 3));
+/// # }
+/// ```
+///
+/// Getting the index wrong is a compile error:
+///
+/// ```compile_fail
+/// # use try_utils::*;
+/// # enum Entry { Timeout(u64), Retries(u32) }
+/// # const DEFAULTS: &[Entry] = &[Entry::Timeout(30), Entry::Retries(3)];
+/// const DEFAULT_TIMEOUT: u64 = const_inner!(DEFAULTS[1], if Entry::Timeout);
+/// # fn main() { let _ = DEFAULT_TIMEOUT; }
+/// ```
+#[macro_export]
+macro_rules! const_inner {
+    ($x:expr, if $i:path) => {
+        const {
+            match $x {
+                $i(q) => q,
+                _ => ::core::panic!(
+                    "{}",
+                    $crate::__inner_expr!(
+                        concat!("Expected '", stringify!($i), "' inside '"),
+                        $x,
+                        "'"
+                    )
+                ),
+            }
+        }
+    };
+}
+
+/// Finds the first item of an array or slice holding the given variant,
+/// returning `Some` of its payload or `None` if there is none.
+///
+/// Like `first_inner!`, the collection is consumed with `IntoIterator`,
+/// so an array is moved and the payload taken by value. Put `ref` in front
+/// to only borrow it and get a reference to the payload instead. The
+/// borrow is taken as a slice, so `ref` works the same on an array, a
+/// `Vec` or a value that is already a `&[T]`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let basket = [Fruit::Orange(1), Fruit::Apple(2), Fruit::Apple(3)];
+/// assert_eq!(scan_inner!(ref basket, if Fruit::Apple), Some(&2));
+/// assert_eq!(scan_inner!(ref basket[..1], if Fruit::Apple), None);
+/// assert_eq!(scan_inner!(basket, if Fruit::Orange), Some(1));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! scan_inner {
+    (ref $v:expr, if $i:path) => {
+        ::core::iter::Iterator::find_map(&mut <[_]>::iter(&$v), |item| match item {
+            $i(q) => ::core::option::Option::Some(q),
+            _ => ::core::option::Option::None,
+        })
+    };
+
+    ($v:expr, if $i:path) => {
+        ::core::iter::Iterator::find_map(&mut ::core::iter::IntoIterator::into_iter($v), |item| {
+            match item {
+                $i(q) => ::core::option::Option::Some(q),
+                _ => ::core::option::Option::None,
+            }
+        })
+    };
+}
+
+/// Descends into a borrowed enum whose payload is string-like, giving a
+/// `Cow` that borrows the payload.
+///
+/// The payload is dereferenced, so a `String` payload gives a `Cow<str>`,
+/// a `Vec<T>` a `Cow<[T]>`, and a `PathBuf` a `Cow<Path>`. The `else`
+/// value is converted with `Into`, so it can be a borrowed default or an
+/// owned value. Without an `else` clause, it panics like `inner!`.
+/// Requires the `alloc` feature (enabled by default).
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::borrow::Cow;
+///
+/// enum Msg {
+///     Text(String),
+///     Ping,
+/// }
+///
+/// let msg = Msg::Text("hello".to_string());
+/// let text: Cow<str> = inner_cow!(&msg, if Msg::Text);
+/// assert!(matches!(text, Cow::Borrowed("hello")));
+///
+/// let text: Cow<str> = inner_cow!(&Msg::Ping, if Msg::Text, else format!("{}", "ping"));
+/// assert!(matches!(text, Cow::Owned(_)));
+/// # }
+/// ```
+#[cfg(feature = "alloc")]
+#[macro_export]
+macro_rules! inner_cow {
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match $x {
+            $i(q) => $crate::__private::Cow::Borrowed(&**q),
+            _ => ::core::convert::Into::into($b),
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match $x {
+            $i(q) => $crate::__private::Cow::Borrowed(&**q),
+            ref n => {
+                $crate::__inner_unexpected!("inner_cow!", $x, n, $crate::__private::type_of(n), $i)
+            }
+        }
+    }};
+}
+
+/// Descends into an `Option<Box<T>>`, or anything else whose `IntoResult`
+/// gives a `Box`, and moves the `T` out of the box.
+///
+/// The `else` value is a `T` rather than a box. Without an `else` clause,
+/// it panics like `inner!`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// struct Node {
+///     value: i32,
+///     next: Option<Box<Node>>,
+/// }
+///
+/// let list = Node { value: 1, next: Some(Box::new(Node { value: 2, next: None })) };
+/// let second: Node = deref_inner!(list.next, else return);
+/// assert_eq!(second.value, 2);
+/// assert_eq!(deref_inner!(second.next, else |_e| Node { value: 0, next: None }).value, 0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! deref_inner {
+    ($x:expr, else |$e:pat| $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => *q,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, else $b:expr) => {{
+        match $crate::IntoResult::into_result($x) {
+            ::core::result::Result::Ok(q) => *q,
+            ::core::result::Result::Err(_) => $b,
+        }
+    }};
+
+    ($x:expr) => {{
+        match $x {
+            x => {
+                let ty = $crate::__private::type_of(&x);
+                match $crate::IntoResult::into_result(x) {
+                    ::core::result::Result::Ok(q) => *q,
+                    ::core::result::Result::Err(ref e) => {
+                        $crate::__inner_unexpected!("deref_inner!", $x, e, ty)
+                    }
+                }
+            }
+        }
+    }};
+}
+
+/// Borrows a `RefCell` and descends into the variant it holds, giving a
+/// `Ref` to the payload.
+///
+/// This is `Ref::filter_map` with the match written for you. The `else`
+/// clause gets the `Ref` to the whole value back, and has to diverge or
+/// give a `Ref` of the payload type. Without an `else` clause, it panics
+/// like `inner!`. As with `RefCell::borrow`, it also panics if the cell
+/// is mutably borrowed.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::cell::{Ref, RefCell};
+///
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let cell = RefCell::new(Fruit::Apple(3));
+/// let apple: Ref<i32> = borrow_inner!(cell, if Fruit::Apple);
+/// assert_eq!(*apple, 3);
+/// drop(apple);
+///
+/// *cell.borrow_mut() = Fruit::Orange(1);
+/// for _ in 0..1 {
+///     let _apple = borrow_inner!(cell, if Fruit::Apple, else continue);
+///     unreachable!();
+/// }
+/// # }
+/// ```
+#[macro_export]
+macro_rules! borrow_inner {
+    ($x:expr, if $i:path, else |$e:pat| $b:expr) => {{
+        match ::core::cell::Ref::filter_map(::core::cell::RefCell::borrow(&$x), |v| match v {
+            $i(q) => ::core::option::Option::Some(q),
+            _ => ::core::option::Option::None,
+        }) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match ::core::cell::Ref::filter_map(::core::cell::RefCell::borrow(&$x), |v| match v {
+            $i(q) => ::core::option::Option::Some(q),
+            _ => ::core::option::Option::None,
+        }) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(_) => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match ::core::cell::Ref::filter_map(::core::cell::RefCell::borrow(&$x), |v| match v {
+            $i(q) => ::core::option::Option::Some(q),
+            _ => ::core::option::Option::None,
+        }) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(r) => {
+                let n = &*r;
+                $crate::__inner_unexpected!(
+                    "borrow_inner!",
+                    $x,
+                    n,
+                    $crate::__private::type_of(n),
+                    $i
+                )
+            }
+        }
+    }};
+}
+
+/// Mutably borrows a `RefCell` and descends into the variant it holds,
+/// giving a `RefMut` to the payload.
+///
+/// This is `RefMut::filter_map`, with the same clauses as `borrow_inner!`.
+/// It panics if the cell is already borrowed, as `RefCell::borrow_mut`
+/// does.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::cell::RefCell;
+///
+/// enum Fruit {
+///     Apple(i32),
+///     Orange(i16),
+/// }
+///
+/// let cell = RefCell::new(Fruit::Apple(3));
+/// *borrow_inner_mut!(cell, if Fruit::Apple) += 1;
+/// assert_eq!(*borrow_inner!(cell, if Fruit::Apple), 4);
+/// # let _ = Fruit::Orange(0);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! borrow_inner_mut {
+    ($x:expr, if $i:path, else |$e:pat| $b:expr) => {{
+        match ::core::cell::RefMut::filter_map(
+            ::core::cell::RefCell::borrow_mut(&$x),
+            |v| match v {
+                $i(q) => ::core::option::Option::Some(q),
+                _ => ::core::option::Option::None,
+            },
+        ) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path, else $b:expr) => {{
+        match ::core::cell::RefMut::filter_map(
+            ::core::cell::RefCell::borrow_mut(&$x),
+            |v| match v {
+                $i(q) => ::core::option::Option::Some(q),
+                _ => ::core::option::Option::None,
+            },
+        ) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(_) => $b,
+        }
+    }};
+
+    ($x:expr, if $i:path) => {{
+        match ::core::cell::RefMut::filter_map(
+            ::core::cell::RefCell::borrow_mut(&$x),
+            |v| match v {
+                $i(q) => ::core::option::Option::Some(q),
+                _ => ::core::option::Option::None,
+            },
+        ) {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err(r) => {
+                let n = &*r;
+                $crate::__inner_unexpected!(
+                    "borrow_inner_mut!",
+                    $x,
+                    n,
+                    $crate::__private::type_of(n),
+                    $i
+                )
+            }
+        }
+    }};
+}
+
+/// Downcasts a `Box<dyn Any>` to the given type, moving the value out.
+///
+/// With `else |e|`, `e` is the original box when it holds something else,
+/// so it can be tried as another type or handed back. This works for
+/// `Box<dyn Any + Send>` too, which is what `catch_unwind` and
+/// `JoinHandle::join` give for a panic payload.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # fn main() {
+/// use std::any::Any;
+///
+/// fn describe(boxed: Box<dyn Any>) -> String {
+///     let n = any_inner!(boxed, as i32, else |e| {
+///         return any_inner!(e, as String, else "something else".to_string());
+///     });
+///     format!("the number {}", n)
+/// }
+///
+/// assert_eq!(describe(Box::new(3)), "the number 3");
+/// assert_eq!(describe(Box::new("hi".to_string())), "hi");
+/// assert_eq!(describe(Box::new(())), "something else");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! any_inner {
+    ($x:expr, as $t:ty, else |$e:pat| $b:expr) => {{
+        match $x.downcast::<$t>() {
+            ::core::result::Result::Ok(q) => *q,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, as $t:ty, else $b:expr) => {{
+        match $x.downcast::<$t>() {
+            ::core::result::Result::Ok(q) => *q,
+            ::core::result::Result::Err(_) => $b,
+        }
+    }};
+}
+
+/// Reads a file into a `String`, panicking with the path and the I/O error
+/// if that fails. Meant for scripts, tests and build tools.
+///
+/// `path` can be anything that implements `AsRef<Path>`. Requires the
+/// `std` feature (enabled by default).
+///
+/// # Examples
+///
+/// ```should_panic
+/// # use try_utils::*;
+/// let config = inner_file!("config.toml");
+/// // thread "main" panicked at "Failed to read "config.toml": No such file or directory (os error 2)"
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! inner_file {
+    ($path:expr) => {{
+        let path = $path;
+        let path: &::std::path::Path = ::std::convert::AsRef::as_ref(&path);
+        $crate::inner!(::std::fs::read_to_string(path), else |e| {
+            $crate::__inner_deny_panic!("inner_file!", "use `inner!(std::fs::read_to_string(path), else ...)`");
+            ::std::panic!("Failed to read {:?}: {}", path, e)
+        })
+    }};
+}
+
+/// Awaits a future and descends into its output, running the `else` clause
+/// if the future does not complete within the timeout.
+///
+/// Requires the `tokio` feature. The timeout is a `std::time::Duration` and
+/// is applied with `tokio::time::timeout`, so the future must be awaited
+/// from within a tokio runtime with the time driver enabled.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "tokio")]
+/// # fn main() {
+/// # use std::time::Duration;
+/// # async fn fetch() -> Result<u32, &'static str> { Ok(7) }
+/// # async fn run(mut rx: tokio::sync::mpsc::Receiver<u32>) -> Result<u32, Option<&'static str>> {
+/// let data = inner_await!(rx.recv(), if Some, timeout Duration::from_millis(100), else return Err(None));
+/// let more = inner_await!(fetch(), timeout Duration::from_secs(1), else |e| return Err(e));
+/// # Ok(data + more)
+/// # }
+/// # let (tx, rx) = tokio::sync::mpsc::channel(1);
+/// # tx.try_send(1).unwrap();
+/// # let rt = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+/// # assert_eq!(rt.block_on(run(rx)), Ok(8));
+/// # }
+/// # #[cfg(not(feature = "tokio"))]
+/// # fn main() {}
+/// ```
+///
+/// The `else |e|` forms are only available without an `if` clause, and `e` is
+/// `None` on timeout and `Some(err)` if the output converted into an `Err`.
+#[cfg(feature = "tokio")]
+#[macro_export]
+macro_rules! inner_await {
+    ($x:expr, if $i:path, timeout $d:expr, else $b:expr) => {{
+        match $crate::__private::tokio::time::timeout($d, $x).await {
+            ::core::result::Result::Ok($i(q)) => q,
+            _ => $b,
+        }
+    }};
+
+    ($x:expr, timeout $d:expr, else |$e:pat| $b:expr) => {{
+        match match $crate::__private::tokio::time::timeout($d, $x).await {
+            ::core::result::Result::Ok(v) => {
+                $crate::IntoResult::into_result(v).map_err(::core::option::Option::Some)
+            }
+            ::core::result::Result::Err(_) => {
+                ::core::result::Result::Err(::core::option::Option::None)
+            }
+        } {
+            ::core::result::Result::Ok(q) => q,
+            ::core::result::Result::Err($e) => $b,
+        }
+    }};
+
+    ($x:expr, timeout $d:expr, else $b:expr) => {{
+        match $crate::__private::tokio::time::timeout($d, $x)
+            .await
+            .map($crate::IntoResult::into_result)
+        {
+            ::core::result::Result::Ok(::core::result::Result::Ok(q)) => q,
+            _ => $b,
+        }
+    }};
+}
+
+/// Filters a `futures::Stream` down to the payloads of the items holding
+/// the given variant, with the `futures` feature.
+///
+/// The result is an `impl Stream` of the payloads, which is `Unpin` if the
+/// input stream is. The other items are dropped.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # enum Event { Data(Vec<u8>), Close }
+/// # #[cfg(feature = "futures")]
+/// # async fn run(events: impl futures::stream::Stream<Item = Event> + Unpin, received: &mut Vec<u8>) {
+/// # use futures::stream::StreamExt;
+/// let mut data = stream_filter_inner!(events, if Event::Data);
+/// while let Some(bytes) = data.next().await {
+///     received.extend_from_slice(&bytes);
+/// }
+/// # }
+/// # fn main() {
+/// # let _ = Event::Close;
+/// # }
+/// ```
+#[cfg(feature = "futures")]
+#[macro_export]
+macro_rules! stream_filter_inner {
+    ($st:expr, if $i:path) => {
+        $crate::__private::futures::stream::StreamExt::filter_map($st, |item| {
+            ::core::future::ready(match item {
+                $i(q) => ::core::option::Option::Some(q),
+                _ => ::core::option::Option::None,
+            })
+        })
+    };
+}
+
+/// Descends into a `serde_json::Value` holding a string, returning `&str`.
+///
+/// Requires the `serde_json` feature. Like `inner!`, it panics on mismatch
+/// unless given an `else` clause, which can bind the `&Value` with `|v|`.
+/// The panic message names the expected JSON type, and shows the value that
+/// was found, truncated if long.
+///
+/// `json_bool!`, `json_u64!`, `json_i64!`, `json_f64!`, `json_array!` and
+/// `json_object!` work the same way, through the corresponding `as_*`
+/// method of `Value`.
+///
+/// # Examples
+///
+/// ```
+/// # use try_utils::*;
+/// # #[cfg(feature = "serde_json")]
+/// # fn main() {
+/// let v = serde_json::json!({ "name": "apple", "count": 3 });
+/// assert_eq!(json_str!(v["name"]), "apple");
+/// assert_eq!(json_u64!(v["count"]), 3);
+/// assert_eq!(json_str!(v["count"], else "unknown"), "unknown");
+/// // json_str!(v["count"]) panics with
+/// // "expected a JSON string inside 'v["count"]', found 3"
+/// # }
+/// # #[cfg(not(feature = "serde_json"))]
+/// # fn main() {}
+/// ```
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_str {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_str, "a JSON string", $($t)+)
+    };
+}
+
+/// Descends into a `serde_json::Value` holding a bool, returning `bool`.
+/// Requires the `serde_json` feature. See `json_str!` for the accepted forms.
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_bool {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_bool, "a JSON bool", $($t)+)
+    };
+}
+
+/// Descends into a `serde_json::Value` holding an unsigned integer, returning `u64`.
+/// Requires the `serde_json` feature. See `json_str!` for the accepted forms.
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_u64 {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_u64, "a JSON unsigned integer", $($t)+)
+    };
+}
+
+/// Descends into a `serde_json::Value` holding an integer, returning `i64`.
+/// Requires the `serde_json` feature. See `json_str!` for the accepted forms.
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_i64 {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_i64, "a JSON integer", $($t)+)
+    };
+}
+
+/// Descends into a `serde_json::Value` holding a number, returning `f64`.
+/// Requires the `serde_json` feature. See `json_str!` for the accepted forms.
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_f64 {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_f64, "a JSON number", $($t)+)
+    };
+}
+
+/// Descends into a `serde_json::Value` holding an array, returning `&Vec<Value>`.
+/// Requires the `serde_json` feature. See `json_str!` for the accepted forms.
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_array {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_array, "a JSON array", $($t)+)
+    };
+}
+
+/// Descends into a `serde_json::Value` holding an object, returning `&Map<String, Value>`.
+/// Requires the `serde_json` feature. See `json_str!` for the accepted forms.
+#[cfg(feature = "serde_json")]
+#[macro_export]
+macro_rules! json_object {
+    ($($t:tt)+) => {
+        $crate::__inner_json!(as_object, "a JSON object", $($t)+)
+    };
+}
+
+#[cfg(feature = "serde_json")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_json {
+    ($m:ident, $expected:expr, $v:expr, else |$e:pat| $b:expr) => {{
+        match &$v {
+            v => {
+                let v: &$crate::__private::serde_json::Value = v;
+                match v.$m() {
+                    ::core::option::Option::Some(q) => q,
+                    ::core::option::Option::None => {
+                        let $e = v;
+                        $b
+                    }
+                }
+            }
+        }
+    }};
+
+    ($m:ident, $expected:expr, $v:expr, else $b:expr) => {{
+        match $crate::__private::serde_json::Value::$m(&$v) {
+            ::core::option::Option::Some(q) => q,
+            ::core::option::Option::None => $b,
+        }
+    }};
+
+    ($m:ident, $expected:expr, $v:expr) => {{
+        match &$v {
+            v => {
+                let v: &$crate::__private::serde_json::Value = v;
+                match v.$m() {
+                    ::core::option::Option::Some(q) => q,
+                    ::core::option::Option::None => {
+                        $crate::__inner_deny_panic!("json_*!", "add an `else` clause");
+                        $crate::__private::json_mismatch($expected, $crate::__inner_expr!($v), v)
+                    }
+                }
+            }
+        }
+    }};
+}
+
+/// Panics for a value that could not be descended into. `$found` is a
+/// reference to what was found instead, which is included in the message
+/// if it implements `Debug`, `$ty` is the `TypeOf` of the scrutinee, and `$i`
+/// is the expected variant for the `if` clause forms. Callers that cannot
+/// capture the variant as a `path` pass its name with `@named` instead.
+/// `$form` names the calling macro, for the error of `deny_panic`.
+/// The message is formatted out of line, in cold functions, so a call site
+/// only pays for a call on its failure path.
+///
+/// With `defmt`, the panic goes through `defmt::panic!` instead, so that
+/// no `core::fmt` code is involved.
+#[cfg(not(feature = "defmt"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_unexpected {
+    ($form:tt, $x:expr, $found:expr, $ty:expr) => {{
+        $crate::__inner_deny_panic!($form);
+        $crate::__private::unexpected(
+            $crate::__inner_expr!($x),
+            $ty.name(),
+            $crate::__inner_found!($found),
+        )
+    }};
+    ($form:tt, $x:expr, $found:expr, $ty:expr, @named $name:expr) => {{
+        $crate::__inner_deny_panic!($form);
+        $crate::__private::expected(
+            $name,
+            $crate::__inner_expr!($x),
+            $ty.name(),
+            $crate::__inner_found!($found),
+        )
+    }};
+    ($form:tt, $x:expr, $found:expr, $ty:expr, $i:path) => {
+        $crate::__inner_unexpected!($form, $x, $found, $ty, @named stringify!($i))
+    };
+}
+
+#[cfg(feature = "defmt")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_unexpected {
+    ($form:tt, $x:expr, $found:expr, $ty:expr) => {{
+        let _ = $found;
+        $crate::__inner_deny_panic!($form);
+        ::defmt::panic!(
+            "Unexpected value found inside '{=str}' (type {=str})",
+            $crate::__inner_expr!($x),
+            $ty.name()
+        )
+    }};
+    ($form:tt, $x:expr, $found:expr, $ty:expr, @named $name:expr) => {{
+        let _ = $found;
+        $crate::__inner_deny_panic!($form);
+        ::defmt::panic!(
+            "Expected '{=str}' inside '{=str}' (type {=str})",
+            $name,
+            $crate::__inner_expr!($x),
+            $ty.name()
+        )
+    }};
+    ($form:tt, $x:expr, $found:expr, $ty:expr, $i:path) => {
+        $crate::__inner_unexpected!($form, $x, $found, $ty, @named stringify!($i))
+    };
+}
+
+/// The constant panic of `inner!(const ..)`. The message is built at
+/// compile time, so what was found and the type are left out, and it goes
+/// through `core::panic!` even with `defmt`, which cannot panic in a
+/// constant.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_const_unexpected {
+    ($x:expr) => {{
+        $crate::__inner_deny_panic!("inner!");
+        ::core::panic!(
+            "{}",
+            $crate::__inner_expr!("Unexpected value found inside '", $x, "'")
+        )
+    }};
+    ($x:expr, @named $name:expr) => {{
+        $crate::__inner_deny_panic!("inner!");
+        ::core::panic!(
+            "{}",
+            $crate::__inner_expr!(concat!("Expected '", $name, "' inside '"), $x, "'")
+        )
+    }};
+}
+
+/// Checks that the pattern inside an `if` clause variant only consists of
+/// bindings and tuples of them, which is what can be given back as an
+/// expression, and expands to the match given first if it does. A group
+/// is checked by putting its contents in front, with `;` where it ends.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_bindings {
+    (@elem $out:tt [() $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@sep $out [$($rest)*])
+    };
+    (@elem $out:tt [($($group:tt)+) $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@elem $out [$($group)+ ; $($rest)*])
+    };
+    (@elem $out:tt [ref $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@reject ref)
+    };
+    (@elem $out:tt [mut $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@reject mut)
+    };
+    (@elem $out:tt [$q:ident $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@sep $out [$($rest)*])
+    };
+    (@elem $out:tt [$t:tt $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@reject $t)
+    };
+    (@elem $out:tt []) => {
+        $crate::__inner_bindings!(@reject ,)
+    };
+
+    (@sep $out:tt []) => { $out };
+    (@sep $out:tt [,]) => { $out };
+    (@sep $out:tt [, ; $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@sep $out [$($rest)*])
+    };
+    (@sep $out:tt [, $($rest:tt)+]) => {
+        $crate::__inner_bindings!(@elem $out [$($rest)+])
+    };
+    (@sep $out:tt [; $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@sep $out [$($rest)*])
+    };
+    (@sep $out:tt [$t:tt $($rest:tt)*]) => {
+        $crate::__inner_bindings!(@reject $t)
+    };
+
+    (@reject $t:tt) => {
+        compile_error!(concat!(
+            "the pattern inside the variant may only consist of bindings and tuples of them, ",
+            "found `", stringify!($t), "`; for other patterns, use ",
+            "`inner!(if let <pattern> = <value> => <output>)`"
+        ))
+    };
+}
+
+/// Checks at compile time that the value after `=` in `inner!(x, if let
+/// V(v) = x)` is the one given first.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_same_expr {
+    ($x:expr, $e:expr) => {
+        const _: () = ::core::assert!(
+            $crate::__private::str_eq(stringify!($x), stringify!($e)),
+            "the value after `=` in `if let` must be the one given first, e g `inner!(x, if let Some(v) = x)`"
+        );
+    };
+}
+
+/// Stringifies an expression for a diagnostic, on one line and cut down
+/// to `MAX_EXPR_LEN` characters at compile time. Given a literal before and
+/// after it, the whole message is built as one string, for the constant
+/// panics of `inner!(const ..)`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_expr {
+    ($x:expr) => {
+        $crate::__inner_expr!("", $x, "")
+    };
+    ($before:expr, $x:expr, $after:expr) => {{
+        const LEN: usize = $crate::__private::expr_message_len($before, stringify!($x), $after);
+        const BYTES: [u8; LEN] = $crate::__private::expr_message($before, stringify!($x), $after);
+        const MESSAGE: &str = $crate::__private::utf8(&BYTES);
+        MESSAGE
+    }};
+}
+
+/// Placed in front of every panic that a macro may expand to. With the
+/// `deny_panic` feature, it turns the panicking form into a compile error
+/// explaining what to use instead; otherwise it expands to nothing. Given
+/// only the name of the macro, it picks the advice for a missing `else`.
+#[cfg(feature = "deny_panic")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_deny_panic {
+    ("inner!") => {
+        $crate::__inner_deny_panic!("inner!", "add an `else` clause, or use `ok!` or `some!`")
+    };
+    ("inner_debug_checked!") => {
+        $crate::__inner_deny_panic!("inner_debug_checked!", "use `inner!` with an `else` clause")
+    };
+    ($form:literal) => {
+        $crate::__inner_deny_panic!($form, "add an `else` clause")
+    };
+    ($form:expr, $instead:expr) => {
+        compile_error!(concat!(
+            "this form of `",
+            $form,
+            "` panics, which the `deny_panic` feature of try_utils forbids; ",
+            $instead,
+            " instead"
+        ))
+    };
+}
+
+#[cfg(not(feature = "deny_panic"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_deny_panic {
+    ($form:expr $(, $instead:expr)?) => {};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_found {
+    ($found:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{DebugFallback, DebugUnit, DebugViaDebug};
+        (&&&$crate::__private::DebugWrap($found)).as_debug()
+    }};
+}
+
+#[cfg(feature = "alloc")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_format {
+    ($($f:tt)+) => {
+        $crate::__private::format!($($f)+)
+    };
+}
+
+#[cfg(not(feature = "alloc"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_format {
+    ($($f:tt)+) => {
+        compile_error!("the `or_fmt` clause requires the `alloc` feature of try_utils")
+    };
+}
+
+#[cfg(feature = "anyhow")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_context {
+    ($found:expr, $expected:expr, $x:expr, $($c:tt)+) => {{
+        $crate::__private::context_error(
+            format_args!($($c)+),
+            $crate::__private::Message {
+                expr: $x,
+                expected: $expected,
+                found: $crate::__inner_found!($found),
+            },
+        )
+    }};
+}
+
+#[cfg(not(feature = "anyhow"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_context {
+    ($($t:tt)*) => {
+        compile_error!("the `context` clause requires the `anyhow` feature of try_utils")
+    };
+}
+
+#[cfg(feature = "log")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_log {
+    ($lvl:ident, $found:expr, $expected:expr, $x:expr) => {{
+        $crate::__private::log::$lvl!(
+            target: module_path!(),
+            "{}",
+            $crate::__private::Message {
+                expr: $x,
+                expected: $expected,
+                found: $crate::__inner_found!($found),
+            }
+        )
+    }};
+}
+
+#[cfg(not(feature = "log"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_log {
+    ($($t:tt)*) => {
+        compile_error!("the `else log` clause requires the `log` feature of try_utils")
+    };
+}
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_event {
+    ($lvl:ident, $found:expr, $expected:expr, $x:expr) => {{
+        let expected: ::core::option::Option<&str> = $expected;
+        let found = $crate::__inner_found!($found);
+        $crate::__private::tracing::event!(
+            $crate::__inner_tracing_level!($lvl),
+            expr = $x,
+            expected,
+            found_variant = found.map($crate::__private::tracing::field::debug),
+            location = concat!(file!(), ":", line!(), ":", column!()),
+            "{}",
+            $crate::__private::Message {
+                expr: $x,
+                expected,
+                found,
+            }
+        )
+    }};
+}
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_tracing_level {
+    (trace) => {
+        $crate::__private::tracing::Level::TRACE
+    };
+    (debug) => {
+        $crate::__private::tracing::Level::DEBUG
+    };
+    (info) => {
+        $crate::__private::tracing::Level::INFO
+    };
+    (warn) => {
+        $crate::__private::tracing::Level::WARN
+    };
+    (error) => {
+        $crate::__private::tracing::Level::ERROR
+    };
+    ($other:ident) => {
+        compile_error!(concat!(
+            "unknown level `",
+            stringify!($other),
+            "`, expected one of trace, debug, info, warn or error"
+        ))
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __inner_event {
+    ($($t:tt)*) => {
+        compile_error!("the `event` clause requires the `tracing` feature of try_utils")
+    };
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use core::fmt;
+    use core::marker::PhantomData;
+
+    #[cfg(feature = "alloc")]
+    pub use alloc::borrow::Cow;
+    #[cfg(feature = "alloc")]
+    pub use alloc::boxed::Box;
+    #[cfg(feature = "alloc")]
+    pub use alloc::format;
+    #[cfg(feature = "alloc")]
+    pub use alloc::vec::Vec;
+
+    #[cfg(feature = "futures")]
+    pub use futures;
+    #[cfg(feature = "log")]
+    pub use log;
+    #[cfg(feature = "rayon")]
+    pub use rayon;
+    #[cfg(feature = "serde_json")]
+    pub use serde_json;
+    #[cfg(feature = "tokio")]
+    pub use tokio;
+    #[cfg(feature = "tracing")]
+    pub use tracing;
+
+    /// Borrows a value so that it can be rendered with `Debug` if, and only
+    /// if, its type implements it (autoderef specialization). `()`, which is
+    /// what `Option` converts into on `None`, renders as nothing at all.
+    pub struct DebugWrap<'a, T>(pub &'a T);
+
+    pub trait DebugUnit<'a> {
+        fn as_debug(&self) -> Option<&'a dyn fmt::Debug>;
+    }
+
+    impl<'a> DebugUnit<'a> for &&DebugWrap<'a, ()> {
+        #[inline]
+        fn as_debug(&self) -> Option<&'a dyn fmt::Debug> {
+            None
+        }
+    }
+
+    pub trait DebugViaDebug<'a> {
+        fn as_debug(&self) -> Option<&'a dyn fmt::Debug>;
+    }
+
+    impl<'a, T: fmt::Debug> DebugViaDebug<'a> for &DebugWrap<'a, T> {
+        #[inline]
+        fn as_debug(&self) -> Option<&'a dyn fmt::Debug> {
+            Some(self.0)
+        }
+    }
+
+    pub trait DebugFallback<'a> {
+        fn as_debug(&self) -> Option<&'a dyn fmt::Debug>;
+    }
+
+    impl<'a, T> DebugFallback<'a> for DebugWrap<'a, T> {
+        #[inline]
+        fn as_debug(&self) -> Option<&'a dyn fmt::Debug> {
+            None
+        }
+    }
+
+    const ELLIPSIS: &str = "…";
+
+    /// Steps over one byte of a stringified expression, or over a run of
+    /// whitespace with a line break in it, which reads as a single space.
+    const fn expr_step(expr: &[u8], i: usize) -> (u8, usize) {
+        let mut j = i;
+        let mut line_break = false;
+        while j < expr.len() && expr[j].is_ascii_whitespace() {
+            line_break |= expr[j] == b'\n';
+            j += 1;
+        }
+        if line_break {
+            (b' ', j)
+        } else {
+            (expr[i], i + 1)
+        }
+    }
+
+    /// Renders `before`, `expr` and `after` into a buffer, with `expr` on
+    /// one line and cut to `MAX_EXPR_LEN` characters. Returns the buffer
+    /// and the full length, so that an empty buffer can be used to size
+    /// the real one.
+    const fn expr_render<const N: usize>(
+        before: &str,
+        expr: &str,
+        after: &str,
+    ) -> ([u8; N], usize) {
+        const fn push<const N: usize>(buf: &mut [u8; N], at: usize, b: u8) -> usize {
+            if at < N {
+                buf[at] = b;
+            }
+            at + 1
+        }
+
+        const fn push_str<const N: usize>(buf: &mut [u8; N], mut at: usize, s: &str) -> usize {
+            let s = s.as_bytes();
+            let mut i = 0;
+            while i < s.len() {
+                at = push(buf, at, s[i]);
+                i += 1;
+            }
+            at
+        }
+
+        let expr = expr.as_bytes();
+        let mut chars = 0;
+        let mut i = 0;
+        while i < expr.len() {
+            let (b, next) = expr_step(expr, i);
+            if b & 0xC0 != 0x80 {
+                chars += 1;
+            }
+            i = next;
+        }
+        let keep = if chars > crate::MAX_EXPR_LEN {
+            crate::MAX_EXPR_LEN - 1
+        } else {
+            chars
+        };
+
+        let mut buf = [0; N];
+        let mut at = push_str(&mut buf, 0, before);
+        let mut kept = 0;
+        i = 0;
+        while i < expr.len() {
+            let (b, next) = expr_step(expr, i);
+            if b & 0xC0 != 0x80 {
+                if kept == keep {
+                    break;
+                }
+                kept += 1;
+            }
+            at = push(&mut buf, at, b);
+            i = next;
+        }
+        if chars > keep {
+            at = push_str(&mut buf, at, ELLIPSIS);
+        }
+        at = push_str(&mut buf, at, after);
+        (buf, at)
+    }
+
+    pub const fn expr_message_len(before: &str, expr: &str, after: &str) -> usize {
+        expr_render::<0>(before, expr, after).1
+    }
+
+    pub const fn expr_message<const N: usize>(before: &str, expr: &str, after: &str) -> [u8; N] {
+        expr_render(before, expr, after).0
+    }
+
+    pub const fn str_eq(a: &str, b: &str) -> bool {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    pub const fn utf8(bytes: &'static [u8]) -> &'static str {
+        match core::str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(_) => panic!("expression cut inside a character"),
+        }
+    }
+
+    /// The collection that `collect_inner!(.., as C)` fills. A
+    /// `heapless::Vec` has inherent methods of the same names, which are
+    /// picked over those of `Extending`, so that a payload that does not
+    /// fit is given back rather than panicking in `Extend`.
+    pub struct Collecting<C>(pub C);
+
+    pub trait Extending<T> {
+        type Output;
+        fn push_item(&mut self, item: T) -> Result<(), T>;
+        fn finish(self, full: Option<T>) -> Self::Output;
+    }
+
+    impl<T, C: Extend<T>> Extending<T> for Collecting<C> {
+        type Output = C;
+
+        #[inline]
+        fn push_item(&mut self, item: T) -> Result<(), T> {
+            self.0.extend(Some(item));
+            Ok(())
+        }
+
+        /// Nothing can be full, so there is nothing to give back.
+        #[inline]
+        fn finish(self, _full: Option<T>) -> C {
+            self.0
+        }
+    }
+
+    #[cfg(feature = "heapless")]
+    impl<T, const N: usize> Collecting<heapless::Vec<T, N>> {
+        #[inline]
+        pub fn push_item(&mut self, item: T) -> Result<(), T> {
+            self.0.push(item)
+        }
+
+        #[inline]
+        pub fn finish(self, full: Option<T>) -> Result<heapless::Vec<T, N>, T> {
+            match full {
+                Some(q) => Err(q),
+                None => Ok(self.0),
+            }
+        }
+    }
+
+    /// Carries the type of a scrutinee without holding on to it, so that
+    /// `type_name` is only looked up once a panic is underway.
+    pub struct TypeOf<T>(PhantomData<fn() -> T>);
+
+    #[inline(always)]
+    pub const fn type_of<T>(_: &T) -> TypeOf<T> {
+        TypeOf(PhantomData)
+    }
+
+    impl<T> TypeOf<T> {
+        #[cold]
+        pub fn name(self) -> &'static str {
+            core::any::type_name::<T>()
+        }
+    }
+
+    /// The message the panicking forms panic with, see the crate
+    /// documentation for the format.
+    pub struct PanicMessage<'a> {
+        pub expr: &'a str,
+        pub ty: &'a str,
+        pub expected: Option<&'a str>,
+        pub found: Option<&'a dyn fmt::Debug>,
+        pub location: &'a core::panic::Location<'a>,
+    }
+
+    impl<'a> fmt::Display for PanicMessage<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            #[cfg(feature = "std")]
+            if let Some(format) = crate::PANIC_FORMATTER.get() {
+                return f.write_str(&format(&crate::PanicContext {
+                    expr: self.expr,
+                    expected: self.expected,
+                    type_name: self.ty,
+                    found: self.found,
+                    location: self.location,
+                }));
+            }
+            match self.expected {
+                Some(expected) => write!(f, "Expected '{}' inside '{}'", expected, self.expr)?,
+                None => write!(f, "Unexpected value found inside '{}'", self.expr)?,
+            }
+            write!(f, " (type {})", self.ty)?;
+            match (self.expected, self.found) {
+                (Some(_), Some(found)) => write!(f, ", found {:?}", found),
+                (None, Some(found)) => write!(f, ": {:?}", found),
+                (_, None) => Ok(()),
+            }
+        }
+    }
+
+    /// Builds the error of `ok!(x, if V, mismatch)`, located at the macro.
+    #[inline]
+    #[track_caller]
+    pub fn mismatch<T>(expr: &'static str, expected: &'static str, found: T) -> crate::Mismatch<T> {
+        crate::Mismatch {
+            expr,
+            expected,
+            found,
+            location: core::panic::Location::caller(),
+        }
+    }
+
+    /// The panic of the panicking forms without an `if` clause. Kept out of
+    /// line and cold, so that each call site is a single call on the
+    /// failure path rather than inlined formatting.
+    #[cold]
+    #[inline(never)]
+    #[track_caller]
+    pub fn unexpected(expr: &str, ty: &str, found: Option<&dyn fmt::Debug>) -> ! {
+        fail(PanicMessage {
+            expr,
+            ty,
+            expected: None,
+            found,
+            location: core::panic::Location::caller(),
+        })
+    }
+
+    /// The panic of the `if` clause forms, naming the expected variant.
+    #[cold]
+    #[inline(never)]
+    #[track_caller]
+    pub fn expected(expected: &str, expr: &str, ty: &str, found: Option<&dyn fmt::Debug>) -> ! {
+        fail(PanicMessage {
+            expr,
+            ty,
+            expected: Some(expected),
+            found,
+            location: core::panic::Location::caller(),
+        })
+    }
+
+    #[track_caller]
+    fn fail(msg: PanicMessage<'_>) -> ! {
+        panic!("{}", msg)
+    }
+
+    /// Describes a failed extraction. Every diagnostic the macros produce
+    /// (error contexts, log lines) is rendered through this.
+    pub struct Message<'a> {
+        pub expr: &'a str,
+        pub expected: Option<&'a str>,
+        pub found: Option<&'a dyn fmt::Debug>,
+    }
+
+    impl<'a> fmt::Display for Message<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.expected {
+                Some(expected) => write!(f, "expected '{}' inside '{}'", expected, self.expr)?,
+                None => write!(f, "unexpected value found inside '{}'", self.expr)?,
+            }
+            match self.found {
+                Some(found) => write!(f, ", found {:?}", found),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// How much of a JSON value is shown in panic messages.
+    #[cfg(feature = "serde_json")]
+    const JSON_FOUND_MAX: usize = 64;
+
+    #[cfg(feature = "serde_json")]
+    #[cold]
+    #[track_caller]
+    pub fn json_mismatch(expected: &str, expr: &str, found: &serde_json::Value) -> ! {
+        let mut found = found.to_string();
+        if let Some((i, _)) = found.char_indices().nth(JSON_FOUND_MAX) {
+            found.truncate(i);
+            found.push('…');
+        }
+        panic!("expected {} inside '{}', found {}", expected, expr, found)
+    }
+
+    #[cfg(feature = "anyhow")]
+    pub fn context_error(context: fmt::Arguments<'_>, msg: Message<'_>) -> anyhow::Error {
+        anyhow::Error::msg(format!("{}: {}", context, msg))
+    }
+}
+
+#[test]
+fn simple_opt() {
+    assert_eq!(inner!(Some(7)), 7);
+}
+
+#[test]
+#[should_panic(expected = "Unexpected value found inside 'z'")]
+fn simple_opt_fail() {
+    let z: Option<i32> = None;
+    inner!(z);
+}
+
+#[test]
+fn else_clause() {
+    let x: Result<String, i32> = Err(7);
+    let _ = inner!(x, else return);
+    panic!();
+}
+
+#[test]
+fn else_clause_2() {
+    let x: Result<String, i32> = Err(7);
+    let y = inner!(x, else |e| {
+        assert_eq!(e, 7);
+        (e + 2).to_string()
+    });
+    assert_eq!(&y, "9");
+}
+
+#[test]
+fn apple() {
+    enum Fruit {
+        Apple(i32),
+        _Orange(i16),
+    }
+    let z = Fruit::Apple(15);
+    assert_eq!(15, inner!(z, if Fruit::Apple));
+}
+
+#[test]
+fn if_else() {
+    enum Fruit {
+        Apple(i32),
+        _Orange(i16),
+    }
+    let z = Fruit::Apple(15);
+    assert_eq!(15, inner!(z, if Fruit::Apple, else panic!("Not an apple")));
+}
+
+#[test]
+fn own_enum() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    impl IntoResult<i32, i16> for Fruit {
+        fn into_result(self) -> Result<i32, i16> {
+            match self {
+                Fruit::Apple(i) => Ok(i),
+                Fruit::Orange(i) => Err(i),
+            }
+        }
+    }
+    let z = Fruit::Orange(15);
+    assert_eq!(7, inner!(z, else |e| (e - 8) as i32));
+
+    let z = Fruit::Apple(15);
+    assert_eq!(
+        9,
+        inner!(z, if Fruit::Orange, else |e| {
+            assert_eq!(e, Fruit::Apple(15));
+            9
+        })
+    );
+}
+
+#[test]
+fn some() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    assert_eq!(some!(Fruit::Apple(15), if Fruit::Apple), Some(15));
+    assert_eq!(some!(Fruit::Orange(15), if Fruit::Apple), None);
+    assert_eq!(
+        some!(Fruit::Orange(15), if Fruit::Apple, else |e| {
+            assert_eq!(e, Fruit::Orange(15));
+            Some(30)
+        }),
+        Some(30)
+    );
+}
+
+#[test]
+fn ok() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple), Ok(15));
+
+    assert_eq!(
+        ok!(Fruit::Orange(15), if Fruit::Apple),
+        Err(Fruit::Orange(15))
+    );
+    assert_eq!(
+        ok!(Fruit::Orange(15), if Fruit::Apple, else |e| {
+            assert_eq!(e, Fruit::Orange(15));
+            Err(3)
+        }),
+        Err(3)
+    );
+
+    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Orange, or 67), Err(67));
+    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple, or 67), Ok(15));
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn await_timeout() {
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Msg {
+        Data(i32),
+        _Close,
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let x = inner_await!(async { Msg::Data(5) }, if Msg::Data, timeout Duration::from_secs(5), else 0);
+        assert_eq!(x, 5);
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Msg::Data(5)
+        };
+        let x = inner_await!(slow, if Msg::Data, timeout Duration::from_millis(10), else 7);
+        assert_eq!(x, 7);
+
+        let x: Result<i32, i16> = Err(3);
+        let y = inner_await!(async { x }, timeout Duration::from_secs(5), else |e| {
+            assert_eq!(e, Some(3));
+            9
+        });
+        assert_eq!(y, 9);
+
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<i32, i16>(5)
+        };
+        let y = inner_await!(slow, timeout Duration::from_millis(10), else |e| {
+            assert!(e.is_none());
+            9
+        });
+        assert_eq!(y, 9);
+    });
+}
+
+#[cfg(feature = "anyhow")]
+#[test]
+fn anyhow_context() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Resp {
+        Ok(i32),
+        Busy(u8),
+    }
+
+    struct NoDebug;
+
+    fn handshake(resp: Resp, peer: &str) -> anyhow::Result<i32> {
+        let v = inner!(resp, if Resp::Ok, context "handshake with {}", peer);
+        Ok(v * 2)
+    }
+
+    fn convert(x: Result<i32, NoDebug>) -> anyhow::Result<i32> {
+        let peer = "10.0.0.2";
+        Ok(inner!(x, context "handshake with {peer}"))
+    }
+
+    assert_eq!(handshake(Resp::Ok(4), "10.0.0.1").unwrap(), 8);
+    assert_eq!(
+        handshake(Resp::Busy(3), "10.0.0.1")
+            .unwrap_err()
+            .to_string(),
+        "handshake with 10.0.0.1: expected 'Resp::Ok' inside 'resp', found Busy(3)"
+    );
+    assert_eq!(
+        convert(Err(NoDebug)).unwrap_err().to_string(),
+        "handshake with 10.0.0.2: unexpected value found inside 'x'"
+    );
+
+    let r = ok!(Resp::Busy(1), if Resp::Ok, context "polling");
+    assert_eq!(
+        r.unwrap_err().to_string(),
+        "polling: expected 'Resp::Ok' inside 'Resp::Busy(1)', found Busy(1)"
+    );
+    assert_eq!(ok!(Resp::Ok(1), if Resp::Ok, context "polling").unwrap(), 1);
+}
+
+#[test]
+fn try_new() {
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+
+    fn connect(addr: SocketAddr) -> Option<TcpStream> {
+        let stream = inner!(TryNew(TcpStream::connect(addr)), else |_e| return None);
+        Some(stream)
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let stream = connect(addr).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), addr);
+
+    // Nothing can listen on port 0, so this fails whatever else runs. How
+    // it fails differs between platforms.
+    assert!(connect(SocketAddr::from(([127, 0, 0, 1], 0))).is_none());
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn else_log() {
+    use std::cell::Cell;
+    use std::fmt;
+    use std::sync::Mutex;
+
+    struct Logger(Mutex<Vec<(log::Level, String, String)>>);
+
+    impl log::Log for Logger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+        fn log(&self, record: &log::Record) {
+            let entry = (
+                record.level(),
+                record.target().to_string(),
+                record.args().to_string(),
+            );
+            self.0.lock().unwrap().push(entry);
+        }
+        fn flush(&self) {}
+    }
+
+    static LOGGER: Logger = Logger(Mutex::new(Vec::new()));
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Warn);
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        _Orange(i16),
+    }
+
+    struct Counted<'a>(&'a Cell<u32>);
+
+    impl<'a> fmt::Debug for Counted<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.set(self.0.get() + 1);
+            f.write_str("Counted")
+        }
+    }
+
+    let z = Fruit::_Orange(15);
+    assert_eq!(inner!(z, if Fruit::Apple, else log warn, 3), 3);
+    assert_eq!(
+        inner!(Fruit::Apple(4), if Fruit::Apple, else log warn, 3),
+        4
+    );
+    let y: Option<i32> = None;
+    assert_eq!(inner!(y, else log error, 5), 5);
+
+    let formatted = Cell::new(0);
+    let r: Result<i32, Counted> = Err(Counted(&formatted));
+    assert_eq!(inner!(r, else log debug, 6), 6);
+    assert_eq!(formatted.get(), 0);
+
+    let logged = LOGGER.0.lock().unwrap();
+    assert_eq!(
+        *logged,
+        vec![
+            (
+                log::Level::Warn,
+                module_path!().to_string(),
+                "expected 'Fruit::Apple' inside 'z', found _Orange(15)".to_string()
+            ),
+            (
+                log::Level::Error,
+                module_path!().to_string(),
+                "unexpected value found inside 'y'".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn const_if_forms() {
+    #[derive(Debug, PartialEq)]
+    enum Config {
+        Fixed(i32),
+        Dynamic,
+    }
+
+    const fn or_default(x: Config) -> i32 {
+        inner!(x, if Config::Fixed, else 60)
+    }
+    const fn positive(x: Config) -> i32 {
+        inner!(x, if Config::Fixed, guard |n| n > 0, else |_e| 1)
+    }
+
+    const FIXED: Config = Config::Fixed(30);
+    const TIMEOUT: i32 = or_default(FIXED);
+    const FALLBACK: i32 = or_default(Config::Dynamic);
+    const CLAMPED: i32 = positive(Config::Fixed(-4));
+    assert_eq!((TIMEOUT, FALLBACK, CLAMPED), (30, 60, 1));
+
+    const SOME: Option<i32> = some!(FIXED, if Config::Fixed);
+    const NONE: Option<i32> = some!(Config::Dynamic, if Config::Fixed);
+    assert_eq!((SOME, NONE), (Some(30), None));
+
+    const OK: Result<i32, Config> = ok!(FIXED, if Config::Fixed);
+    const ERR: Result<i32, Config> = ok!(Config::Dynamic, if Config::Fixed);
+    const OR: Result<i32, &str> = ok!(Config::Dynamic, if Config::Fixed, or "dynamic");
+    assert_eq!(OK, Ok(30));
+    assert_eq!(ERR, Err(Config::Dynamic));
+    assert_eq!(OR, Err("dynamic"));
+}
+
+#[test]
+fn const_option_result() {
+    const fn extract(x: Option<i32>) -> i32 {
+        inner!(x, if Some, else { 0 })
+    }
+    const fn parsed(r: Result<u8, u8>) -> u8 {
+        inner!(r, if Ok, else |_e| 100)
+    }
+    const fn checked(r: Result<u8, u8>) -> Option<u8> {
+        some!(r, if Ok)
+    }
+
+    const V: i32 = extract(Some(42));
+    const MISSING: i32 = extract(None);
+    const PARSED: u8 = parsed(Ok(7));
+    const FAILED: u8 = parsed(Err(3));
+    const CHECKED: Option<u8> = checked(Err(1));
+    assert_eq!((V, MISSING), (42, 0));
+    assert_eq!((PARSED, FAILED, CHECKED), (7, 100, None));
+}
+
+#[test]
+fn const_panic() {
+    enum Config {
+        Fixed(i32),
+        _Dynamic,
+    }
+
+    const fn extract(x: Config) -> i32 {
+        inner!(const x, if Config::Fixed)
+    }
+
+    const TIMEOUT: i32 = extract(Config::Fixed(30));
+    assert_eq!(TIMEOUT, 30);
+}
+
+#[test]
+#[should_panic(expected = "Expected 'Some' inside 'z'")]
+fn const_panic_fail() {
+    let z: Option<i32> = None;
+    inner!(const z, if Some);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn read_file() {
+    let path = std::env::temp_dir().join(format!("try_utils_read_file_{}", std::process::id()));
+    std::fs::write(&path, "contents").unwrap();
+    let s = inner_file!(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(s, "contents");
+}
+
+#[cfg(feature = "std")]
+#[test]
+#[should_panic(expected = "Failed to read \"/nonexistent/try_utils\"")]
+fn read_file_fail() {
+    inner_file!("/nonexistent/try_utils");
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_event() {
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata};
+
+    type Fields = BTreeMap<&'static str, String>;
+
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<(Level, Fields)>>>);
+
+    struct Visitor<'a>(&'a mut Fields);
+
+    impl<'a> Visit for Visitor<'a> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), value.to_string());
+        }
+    }
+
+    impl tracing::Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::new();
+            event.record(&mut Visitor(&mut fields));
+            let level = *event.metadata().level();
+            self.0.lock().unwrap().push((level, fields));
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let z = Fruit::Orange(15);
+        let (n, line) = (inner!(z, if Fruit::Apple, event warn, else 0), line!());
+        assert_eq!(n, 0);
+        assert_eq!(
+            inner!(Fruit::Apple(3), if Fruit::Apple, event warn, else 0),
+            3
+        );
+        let x: Result<i32, i16> = Err(5);
+        assert_eq!(inner!(x, event debug, else |e| e as i32 + 1), 6);
+        let missing = std::panic::catch_unwind(|| {
+            let y: Option<i32> = None;
+            inner!(y, event)
+        });
+        assert!(missing.is_err());
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 3);
+
+        let (level, fields) = &events[0];
+        assert_eq!(*level, Level::WARN);
+        assert_eq!(fields["expr"], "z");
+        assert_eq!(fields["expected"], "Fruit::Apple");
+        assert_eq!(fields["found_variant"], "Orange(15)");
+        let column = fields["location"].strip_prefix(&format!("{}:{}:", file!(), line));
+        assert!(
+            column.unwrap().parse::<u32>().is_ok(),
+            "{}",
+            fields["location"]
+        );
+        assert_eq!(
+            fields["message"],
+            "expected 'Fruit::Apple' inside 'z', found Orange(15)"
+        );
+
+        let (level, fields) = &events[1];
+        assert_eq!(*level, Level::DEBUG);
+        assert_eq!(fields["expr"], "x");
+        assert!(!fields.contains_key("expected"));
+        assert_eq!(fields["found_variant"], "5");
+
+        let (level, fields) = &events[2];
+        assert_eq!(*level, Level::ERROR);
+        assert_eq!(fields["expr"], "y");
+        assert!(!fields.contains_key("found_variant"));
+    });
+}
+
+#[test]
+fn split_head() {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Packet {
+        Header(u16),
+        Data(u8),
+    }
+
+    let packets = [Packet::Header(2), Packet::Data(7), Packet::Data(9)];
+    assert_eq!(
+        split_head_inner!(packets[0], if Packet::Header),
+        Some((2, ()))
+    );
+    assert_eq!(split_head_inner!(packets[1], if Packet::Header), None);
+
+    let (len, body) = split_head_inner!(packets[0], if Packet::Header, rest &packets[1..]).unwrap();
+    assert_eq!(len, 2);
+    assert_eq!(body, &[Packet::Data(7), Packet::Data(9)]);
+
+    let mut evaluated = false;
+    let r = split_head_inner!(packets[2], if Packet::Header, rest {
+        evaluated = true;
+        0
+    });
+    assert_eq!(r, None);
+    assert!(!evaluated);
+}
+
+#[cfg(all(feature = "process", unix))]
+#[test]
+fn exit_status() {
+    use std::process::Command;
+
+    fn run(script: &str) -> Result<(), i32> {
+        let status = Command::new("sh").args(["-c", script]).status().unwrap();
+        inner!(status, else |s| {
+            assert!(!s.success());
+            return Err(s.code().unwrap());
+        });
+        Ok(())
+    }
+
+    assert_eq!(run("exit 0"), Ok(()));
+    assert_eq!(run("exit 3"), Err(3));
+}
+
+#[test]
+fn assert_inner() {
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        _Orange(i16),
+    }
+
+    let z = Fruit::Apple(15);
+    assert_inner_eq!(z, Fruit::Apple, 15);
+    assert_inner_eq!(&z, Fruit::Apple, 15);
+    assert_inner_eq!(Some("apple"), Some, "apple");
+}
+
+#[test]
+#[should_panic(
+    expected = "assertion failed: payload of 'Fruit::Apple' inside 'z'\n  expected: 16\n     found: 15"
+)]
+fn assert_inner_payload_fail() {
+    enum Fruit {
+        Apple(i32),
+        _Orange(i16),
+    }
+
+    let z = Fruit::Apple(15);
+    assert_inner_eq!(z, Fruit::Apple, 16);
+}
+
+#[test]
+#[should_panic(expected = "assertion failed: expected 'Fruit::Apple' inside 'z', found Orange(3)")]
+fn assert_inner_variant_fail() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let z = Fruit::Orange(3);
+    assert_inner_eq!(z, Fruit::Apple, 16);
+}
+
+#[cfg(feature = "either")]
+#[test]
+fn either() {
+    let l: Either<i32, &str> = Either::Left(5);
+    let r: Either<i32, &str> = Either::Right("five");
+
+    assert_eq!(inner!(l), 5);
+    assert_eq!(inner!(r, else |e| e.len() as i32), 4);
+    assert_eq!(inner!(RightIsOk(r)), "five");
+    assert_eq!(
+        inner!(RightIsOk(l), else |e| {
+            assert_eq!(e, 5);
+            "none"
+        }),
+        "none"
+    );
+
+    assert_eq!(inner!(l, if crate::Either::Left), 5);
+    assert_eq!(inner!(r, if either::Either::Right), "five");
+    assert_eq!(inner!(r, if Either::Left, else 0), 0);
+
+    assert_eq!(some!(l, if Either::Left), Some(5));
+    assert_eq!(some!(r, if Either::Left), None);
+    assert_eq!(ok!(r, if Either::Right), Ok("five"));
+    assert_eq!(ok!(l, if Either::Right), Err(Either::Left(5)));
+}
+
+#[test]
+fn inner_try() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Fruit {
+        Apple(i32),
+        Rotten(&'static str),
+    }
+
+    impl IntoResult<i32, &'static str> for Fruit {
+        fn into_result(self) -> Result<i32, &'static str> {
+            match self {
+                Fruit::Apple(i) => Ok(i),
+                Fruit::Rotten(s) => Err(s),
+            }
+        }
+    }
+
+    fn weigh(f: Fruit) -> Result<i32, String> {
+        let w = inner_try!(f);
+        Ok(w * 2)
+    }
+
+    assert_eq!(weigh(Fruit::Apple(4)), Ok(8));
+    assert_eq!(weigh(Fruit::Rotten("mold")), Err("mold".to_string()));
+}
+
+#[test]
+fn impl_into_result() {
+    fn lookup(ok: bool) -> impl IntoResult<i32, String> {
+        if ok {
+            Ok(3)
+        } else {
+            Err("missing".to_string())
+        }
+    }
+
+    fn maybe(ok: bool) -> impl IntoResult<&'static str, ()> {
+        if ok {
+            Some("yes")
+        } else {
+            None
+        }
+    }
+
+    assert_eq!(inner!(lookup(true)), 3);
+    assert_eq!(inner!(lookup(false), else |e| e.len() as i32), 7);
+    assert_eq!(inner!(maybe(true)), "yes");
+    assert_eq!(inner!(maybe(false), else "no"), "no");
+
+    fn doubled(ok: bool) -> Result<i32, String> {
+        Ok(inner_try!(lookup(ok)) * 2)
+    }
+    assert_eq!(doubled(true), Ok(6));
+    assert_eq!(doubled(false), Err("missing".to_string()));
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn json() {
+    use serde_json::{json, Value};
+
+    let v = json!({
+        "name": "apple",
+        "count": 3,
+        "delta": -2,
+        "weight": 0.5,
+        "fresh": true,
+        "tags": ["red", "round"],
+        "origin": { "country": "SE" },
+    });
+
+    assert_eq!(json_str!(v["name"]), "apple");
+    assert_eq!(json_u64!(v["count"]), 3);
+    assert_eq!(json_i64!(v["delta"]), -2);
+    assert_eq!(json_f64!(v["weight"]), 0.5);
+    assert!(json_bool!(v["fresh"]));
+    assert_eq!(json_array!(v["tags"]).len(), 2);
+    assert_eq!(json_object!(&v["origin"])["country"], "SE");
+
+    assert_eq!(json_str!(v["count"], else "unknown"), "unknown");
+    assert_eq!(
+        json_u64!(v["name"], else |e| {
+            assert_eq!(e, &json!("apple"));
+            0
+        }),
+        0
+    );
+
+    assert_eq!(inner!(v["name"].clone(), if Value::String), "apple");
+    assert_eq!(
+        inner!(v["count"].clone(), if Value::String, else String::new()),
+        ""
+    );
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn external_crate_variants() {
+    let v = serde_json::Value::String("apple".to_string());
+    let s: String = inner!(v, if serde_json::Value::String);
+    assert_eq!(s, "apple");
+
+    let v = serde_json::json!(["red", "round"]);
+    let tags: Vec<serde_json::Value> = inner!(v, if serde_json::Value::Array);
+    assert_eq!(tags.len(), 2);
+
+    let v = serde_json::json!(3);
+    assert_eq!(
+        inner!(v, if serde_json::Value::String, else |e| e.to_string()),
+        "3"
+    );
+    assert_eq!(
+        some!(serde_json::json!(true), if serde_json::Value::Bool),
+        Some(true)
+    );
+    assert_eq!(
+        ok!(serde_json::Value::Null, if serde_json::Value::Bool),
+        Err(serde_json::Value::Null)
+    );
+
+    let v = serde_json::json!("apple");
+    assert_eq!(inner!(&v, if serde_json::Value::String).len(), 5);
+    assert_eq!(v, "apple");
+}
+
+#[cfg(all(feature = "serde_json", not(feature = "defmt")))]
+#[test]
+#[should_panic(expected = "Expected 'serde_json::Value::String' inside 'serde_json::json!(3)'")]
+fn external_crate_variants_fail() {
+    inner!(serde_json::json!(3), if serde_json::Value::String);
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+#[should_panic(expected = "expected a JSON string inside 'v[\"tags\"]', found [\"red\",\"round\"]")]
+fn json_fail() {
+    let v = serde_json::json!({ "tags": ["red", "round"] });
+    json_str!(v["tags"]);
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+#[should_panic(
+    expected = "expected a JSON array inside 'v', found \"aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa…"
+)]
+fn json_fail_truncated() {
+    let v = serde_json::Value::String("a".repeat(100));
+    json_array!(v);
+}
+
+#[test]
+fn ok_mismatch() {
+    #[derive(Debug, PartialEq)]
+    enum Packet {
+        Ack(u8),
+        Nak(u8),
+    }
+
+    assert_eq!(ok!(Packet::Ack(1), if Packet::Ack, mismatch), Ok(1));
+    let line = line!() + 1;
+    let e = ok!(Packet::Nak(2), if Packet::Ack, mismatch).unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "Expected 'Packet::Ack' inside 'Packet::Nak(2)', found Nak(2)"
+    );
+    assert_eq!((e.location.file(), e.location.line()), (file!(), line));
+    assert_eq!(
+        (e.expr, e.expected, e.found),
+        ("Packet::Nak(2)", "Packet::Ack", Packet::Nak(2))
+    );
+}
+
+// The shape is what other services parse, so it is pinned exactly.
+#[cfg(all(feature = "serde", feature = "serde_json"))]
+#[test]
+fn mismatch_json() {
+    let z: Result<i16, u8> = Err(3);
+    let e = ok!(z, if Ok, mismatch).unwrap_err();
+    assert_eq!(
+        serde_json::to_value(e).unwrap(),
+        serde_json::json!({
+            "expr": "z",
+            "expected": "Ok",
+            "found": { "Err": 3 },
+            "location": format!("{}:{}:{}", e.location.file(), e.location.line(), e.location.column()),
+        })
+    );
+    assert_eq!(
+        serde_json::to_value(MissingValue).unwrap(),
+        serde_json::json!({ "expected": "Some", "found": "None" })
+    );
+}
+
+#[test]
+fn some_flatten() {
+    enum Basket {
+        Fruit(Option<i32>),
+        Empty,
+    }
+
+    assert_eq!(
+        some!(Basket::Fruit(Some(3)), if Basket::Fruit, flatten),
+        Some(3)
+    );
+    assert_eq!(some!(Basket::Fruit(None), if Basket::Fruit, flatten), None);
+    assert_eq!(some!(Basket::Empty, if Basket::Fruit, flatten), None);
+    assert_eq!(some!(Basket::Fruit(None), if Basket::Fruit), Some(None));
+}
+
+#[test]
+fn guards() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    assert_eq!(
+        some!(Fruit::Apple(15), if Fruit::Apple(n) if n > 0),
+        Some(15)
+    );
+    assert_eq!(some!(Fruit::Apple(-1), if Fruit::Apple(n) if n > 0), None);
+    assert_eq!(some!(Fruit::Orange(1), if Fruit::Apple(n) if n > 0), None);
+
+    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple(n) if n > 0), Ok(15));
+    assert_eq!(
+        ok!(Fruit::Apple(-1), if Fruit::Apple(n) if n > 0),
+        Err(Fruit::Apple(-1))
+    );
+    assert_eq!(
+        ok!(Fruit::Orange(1), if Fruit::Apple(n) if n > 0),
+        Err(Fruit::Orange(1))
+    );
+    assert_eq!(
+        some!(Some("apple"), if Some(s) if s.len() > 3),
+        Some("apple")
+    );
+}
+
+#[test]
+fn zip() {
+    fn area(w: Option<i32>, h: Option<i32>) -> i32 {
+        let (w, h) = inner_zip!(w, h, else return -1);
+        w * h
+    }
+
+    assert_eq!(area(None, None), -1);
+    assert_eq!(area(Some(3), None), -1);
+    assert_eq!(area(None, Some(4)), -1);
+    assert_eq!(area(Some(3), Some(4)), 12);
+    assert_eq!(inner_zip!(Some("a"), Some(1), else ("b", 2)), ("a", 1));
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn tokio_try_recv() {
+    use tokio::sync::{broadcast, mpsc, oneshot};
+
+    fn drain(rx: &mut mpsc::Receiver<i32>) -> (Vec<i32>, bool) {
+        let mut got = vec![];
+        loop {
+            let msg = inner!(rx.try_recv(), else |e| match e {
+                mpsc::error::TryRecvError::Empty => return (got, false),
+                mpsc::error::TryRecvError::Disconnected => return (got, true),
+            });
+            got.push(msg);
+        }
+    }
+
+    let (tx, mut rx) = mpsc::channel(4);
+    assert_eq!(some!(rx.try_recv()), None);
+    tx.try_send(1).unwrap();
+    assert_eq!(some!(rx.try_recv()), Some(1));
+    tx.try_send(2).unwrap();
+    assert_eq!(drain(&mut rx), (vec![2], false));
+    tx.try_send(3).unwrap();
+    drop(tx);
+    assert_eq!(drain(&mut rx), (vec![3], true));
+
+    let (tx, mut rx) = oneshot::channel();
+    assert_eq!(some!(rx.try_recv()), None);
+    tx.send("done").unwrap();
+    assert_eq!(inner!(rx.try_recv()), "done");
+
+    let (tx, mut rx) = broadcast::channel(1);
+    tx.send(1).unwrap();
+    tx.send(2).unwrap();
+    let mut lagged = 0;
+    let msg = loop {
+        break inner!(rx.try_recv(), else |e| match e {
+            broadcast::error::TryRecvError::Lagged(n) => {
+                lagged += n;
+                continue;
+            }
+            _ => 0,
+        });
+    };
+    assert_eq!((msg, lagged), (2, 1));
+    assert_eq!(some!(rx.try_recv()), None);
+}
+
+#[test]
+fn some_result() {
+    assert_eq!(some!(Ok::<_, ()>(15)), Some(15));
+    assert_eq!(some!(Err::<i32, _>(15)), None);
+}
+
+#[test]
+fn collect() {
+    #[derive(Debug, PartialEq, Eq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let basket = vec![
+        Fruit::Orange(1),
+        Fruit::Apple(2),
+        Fruit::Orange(3),
+        Fruit::Apple(4),
+        Fruit::Orange(5),
+    ];
+    let mut errors = vec![Fruit::Orange(0)];
+    let apples = collect_inner!(basket, if Fruit::Apple, into errors);
+    assert_eq!(apples, vec![2, 4]);
+    assert_eq!(
+        errors,
+        vec![
+            Fruit::Orange(0),
+            Fruit::Orange(1),
+            Fruit::Orange(3),
+            Fruit::Orange(5)
+        ]
+    );
+
+    let mut none = vec![];
+    let all: Vec<i32> = collect_inner!((1..4).map(Some), if Some, into none);
+    assert_eq!(all, vec![1, 2, 3]);
+    assert!(none.is_empty());
+}
+
+#[test]
+fn error_source() {
+    use std::error::Error;
+    use std::fmt;
+    use std::io;
+
+    #[derive(Debug)]
+    struct LoadError(io::Error);
+
+    impl fmt::Display for LoadError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("could not load")
+        }
+    }
+
+    impl Error for LoadError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    fn root_kind(e: &dyn Error) -> Option<io::ErrorKind> {
+        let source = inner!(e.source(), else return None);
+        let io = inner!(source.downcast_ref::<io::Error>(), else return None);
+        Some(io.kind())
+    }
+
+    let e = LoadError(io::Error::new(io::ErrorKind::NotFound, "gone"));
+    assert_eq!(root_kind(&e), Some(io::ErrorKind::NotFound));
+    assert_eq!(root_kind(&e.0), None);
+}
+
+#[cfg(all(test, not(feature = "defmt")))]
+fn panic_message<F: FnOnce() + std::panic::UnwindSafe>(f: F) -> String {
+    let payload = std::panic::catch_unwind(f).unwrap_err();
+    match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => payload.downcast_ref::<&str>().unwrap().to_string(),
+    }
+}
+
+#[cfg(not(feature = "defmt"))]
+#[test]
+fn panic_debug() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    struct NoDebug;
+
+    assert_eq!(
+        panic_message(|| {
+            let z = Fruit::Orange(15);
+            inner!(z, if Fruit::Apple);
+        }),
+        "Expected 'Fruit::Apple' inside 'z' (type try_utils::panic_debug::Fruit), found Orange(15)"
+    );
+    assert_eq!(
+        panic_message(|| {
+            let z: Result<i32, _> = Err("bad");
+            inner!(z);
+        }),
+        "Unexpected value found inside 'z' (type core::result::Result<i32, &str>): \"bad\""
+    );
+    assert_eq!(
+        panic_message(|| {
+            let z: Option<i32> = None;
+            inner!(z);
+        }),
+        "Unexpected value found inside 'z' (type core::option::Option<i32>)"
+    );
+    assert_eq!(
+        panic_message(|| {
+            let z: Result<i32, NoDebug> = Err(NoDebug);
+            inner!(z);
+        }),
+        "Unexpected value found inside 'z' (type core::result::Result<i32, try_utils::panic_debug::NoDebug>)"
+    );
+
+    let z = Fruit::Apple(1);
+    assert_eq!(inner!(&z, if Fruit::Apple), &1);
+    assert_eq!(inner!(z, if Fruit::Apple), 1);
+}
+
+#[test]
+fn upgrade() {
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[allow(dead_code)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    fn weight(w: &std::rc::Weak<Fruit>) -> Option<i32> {
+        Some(inner_upgrade!(w, if Fruit::Apple, else return None))
+    }
+
+    let apple = Rc::new(Fruit::Apple(15));
+    let weak = Rc::downgrade(&apple);
+    assert_eq!(weight(&weak), Some(15));
+    let orange = Rc::new(Fruit::Orange(3));
+    assert_eq!(weight(&Rc::downgrade(&orange)), None);
+    drop(apple);
+    assert_eq!(weight(&weak), None);
+
+    let apple = Arc::new(Fruit::Apple(15));
+    let weak = Arc::downgrade(&apple);
+    assert_eq!(inner_upgrade!(weak, if Fruit::Apple, else 0), 15);
+    drop(apple);
+    assert_eq!(inner_upgrade!(weak, if Fruit::Apple, else 0), 0);
+}
+
+#[test]
+fn try_lock_spin() {
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    let mutex = Arc::new(Mutex::new(0));
+    let barrier = Arc::new(Barrier::new(2));
+    let holder = {
+        let mutex = mutex.clone();
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            let mut guard = mutex.lock().unwrap();
+            barrier.wait();
+            thread::sleep(Duration::from_millis(20));
+            *guard += 1;
+        })
+    };
+
+    barrier.wait();
+    let mut spins = 0;
+    let mut guard = loop {
+        break inner!(mutex.try_lock(), else {
+            spins += 1;
+            thread::yield_now();
+            continue;
+        });
+    };
+    *guard += 1;
+    drop(guard);
+    holder.join().unwrap();
+
+    assert!(spins > 0);
+    assert_eq!(*mutex.lock().unwrap(), 2);
+}
+
+#[test]
+#[allow(clippy::needless_lifetimes)]
+fn borrowed_lifetimes() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(String),
+    }
+
+    fn apple<'a>(x: &'a Fruit) -> &'a i32 {
+        inner!(x, if Fruit::Apple)
+    }
+
+    fn orange<'a>(x: &'a Fruit, default: &'a str) -> &'a str {
+        inner!(x, if Fruit::Orange, else default)
+    }
+
+    fn first<'a, T>(x: &'a [Option<T>]) -> Option<&'a T> {
+        Some(inner!(&x[0], else return None))
+    }
+
+    fn bump(x: &mut Result<i32, String>) {
+        *inner!(x, else return) += 1;
+    }
+
+    let f = Fruit::Apple(15);
+    assert_eq!(*apple(&f), 15);
+    assert_eq!(orange(&f, "none"), "none");
+    assert_eq!(orange(&Fruit::Orange("navel".to_string()), "none"), "navel");
+    assert_eq!(first(&[Some(1), None]), Some(&1));
+    assert_eq!(first::<i32>(&[None]), None);
+
+    let mut r = Ok(1);
+    bump(&mut r);
+    assert_eq!(r, Ok(2));
+    let e: Result<i32, String> = Err("e".to_string());
+    let v = inner!(&e, else |e| {
+        assert_eq!(e, "e");
+        &0
+    });
+    assert_eq!(*v, 0);
+}
+
+#[test]
+#[should_panic(expected = "Expected 'Fruit::Apple' inside 'z'")]
+fn if_fail() {
+    #[allow(dead_code)]
+    enum Fruit {
+        Apple(i32),
+        _Orange(i16),
+    }
+
+    let z = Fruit::_Orange(3);
+    inner!(z, if Fruit::Apple);
+}
+
+#[test]
+fn try_convert() {
+    fn narrow(v: i64) -> Result<i32, i64> {
+        let n = inner!(TryConvert::<i32, i64>::new(v), else |v| return Err(v));
+        Ok(n)
+    }
+
+    assert_eq!(narrow(42), Ok(42));
+    assert_eq!(narrow(-7), Ok(-7));
+    assert_eq!(narrow(1 << 40), Err(1 << 40));
+
+    let c: char = inner!(TryConvert::new(0x41u32), else { '?' });
+    assert_eq!(c, 'A');
+    let c: char = inner!(TryConvert::new(0xD800u32), else { '?' });
+    assert_eq!(c, '?');
+}
+
+#[test]
+fn u32_to_char() {
+    assert_eq!(inner!(U32ToChar(65u32)), 'A');
+    assert_eq!(inner!(U32ToChar(0xD800), else '?'), '?');
+    assert_eq!(inner!(U32ToChar(0x110000), else '?'), '?');
+
+    let decoded: String = [0x68, 0xD800, 0x69]
+        .iter()
+        .map(|&c| inner!(U32ToChar(c), else '\u{FFFD}'))
+        .collect();
+    assert_eq!(decoded, "h\u{FFFD}i");
+}
+
+#[test]
+fn ensure() {
+    #[derive(Debug, PartialEq)]
+    enum State {
+        Ready(u32),
+        Busy(String),
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct NotReady;
+
+    fn start(state: State) -> Result<&'static str, NotReady> {
+        ensure_inner!(state, if State::Ready, else return Err(NotReady));
+        Ok("started")
+    }
+
+    assert_eq!(start(State::Ready(3)), Ok("started"));
+    assert_eq!(start(State::Busy("x".to_string())), Err(NotReady));
+
+    let states = [
+        State::Busy("a".to_string()),
+        State::Ready(1),
+        State::Ready(2),
+    ];
+    let mut ready = 0;
+    for state in &states {
+        ensure_inner!(state, if State::Ready, else continue);
+        ready += 1;
+    }
+    assert_eq!(ready, 2);
+}
+
+#[test]
+fn destructure() {
+    #[derive(Debug)]
+    enum E {
+        Pair((i32, String)),
+        Empty(()),
+    }
+
+    let e = E::Pair((7, "seven".to_string()));
+    let (a, s) = inner!(e, if E::Pair((a, s)));
+    assert_eq!(a, 7);
+    assert_eq!(s, "seven");
+
+    let e = E::Pair((1, "one".to_string()));
+    let (a, s) = inner!(&e, if E::Pair((a, s)));
+    assert_eq!((*a, s.as_str()), (1, "one"));
+
+    let e = E::Empty(());
+    let (a, s) = inner!(e, if E::Pair((a, s)), else (0, String::new()));
+    assert_eq!((a, s.as_str()), (0, ""));
+
+    let e = E::Empty(());
+    let n = inner!(e, if E::Pair((a, _s)), else |e| {
+        assert!(matches!(e, E::Empty(())));
+        (-1, String::new())
+    });
+    assert_eq!(n.0, -1);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let e = E::Empty(());
+            let (_a, _s) = inner!(e, if E::Pair((_a, _s)));
+        }),
+        "Expected 'E::Pair' inside 'e' (type try_utils::destructure::E), found Empty(())"
+    );
+}
+
+#[cfg(not(feature = "defmt"))]
+#[test]
+fn long_expr_panic() {
+    fn lookup(_: &str) -> Option<Option<u32>> {
+        None
+    }
+
+    assert_eq!(
+        panic_message(|| {
+            inner!(lookup("some key that is quite long").map(|e| e.unwrap_or_default()).filter(|r| *r > 2));
+        }),
+        "Unexpected value found inside 'lookup(\"some key that is quite long\").map(|e| e.unwrap_or_default()).filter(|r|…' (type core::option::Option<u32>)"
+    );
+}
+
+#[test]
+fn long_expr() {
+    use __private::{expr_message, expr_message_len, utf8};
+
+    const SHORT: [u8; expr_message_len("<", "a.b()", ">")] = expr_message("<", "a.b()", ">");
+    assert_eq!(utf8(&SHORT), "<a.b()>");
+    const EXACT: &str =
+        "éééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééé";
+    const EXACT_BYTES: [u8; expr_message_len("", EXACT, "")] = expr_message("", EXACT, "");
+    assert_eq!(utf8(&EXACT_BYTES), EXACT);
+    const LONG: &str =
+        "ééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééééé";
+    const LONG_BYTES: [u8; expr_message_len("", LONG, "")] = expr_message("", LONG, "");
+    assert_eq!(utf8(&LONG_BYTES).chars().count(), MAX_EXPR_LEN);
+    assert!(utf8(&LONG_BYTES).ends_with("é…"));
+    const LINES: [u8; expr_message_len("", "a\n    .b()", "")] =
+        expr_message("", "a\n    .b()", "");
+    assert_eq!(utf8(&LINES), "a .b()");
+}
+
+#[test]
+fn first() {
+    fn head(v: Vec<String>) -> Option<String> {
+        let first = first_inner!(v, else return None);
+        Some(first)
+    }
+
+    assert_eq!(
+        head(vec!["a".to_string(), "b".to_string()]),
+        Some("a".to_string())
+    );
+    assert_eq!(head(vec![]), None);
+
+    let v = vec![3, 4];
+    let first: &i32 = first_inner!(ref v, else |_e| &0);
+    assert_eq!(*first, 3);
+    assert_eq!(v.len(), 2);
+
+    let empty: Vec<i32> = Vec::new();
+    assert_eq!(*first_inner!(ref empty, else &-1), -1);
+}
+
+#[test]
+fn if_let() {
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let x = Some(3);
+    assert_eq!(inner!(if let Some(v) = x => v, else 0), 3);
+    let x: Option<i32> = None;
+    assert_eq!(inner!(if let Some(v) = x => v, else 0), 0);
+
+    let z = Fruit::Orange(4);
+    let n = inner!(if let Fruit::Orange(w) = z => i32::from(w), else |e| {
+        panic!("unexpected {:?}", e)
+    });
+    assert_eq!(n, 4);
+    let z = Fruit::Apple(6);
+    let n = inner!(if let Fruit::Orange(w) = z => i32::from(w), else |e| {
+        assert!(matches!(e, Fruit::Apple(6)));
+        -1
+    });
+    assert_eq!(n, -1);
+
+    let z = Fruit::Apple(5);
+    assert_eq!(inner!(if let Fruit::Apple(w) = &z => w * 2), 10);
+
+    let x = Some(3);
+    assert_eq!(inner!(x, if let Some(v) = x, else 0), 3);
+    let x: Option<i32> = None;
+    assert_eq!(inner!(x, if let Some(v) = x, else 0), 0);
+    let z = Fruit::Apple(6);
+    let n = inner!(z, if let Fruit::Orange(w) = z, else |e| {
+        assert!(matches!(e, Fruit::Apple(6)));
+        -1
+    });
+    assert_eq!(n, -1);
+    let p: Result<(u8, char), ()> = Ok((1, 'a'));
+    assert_eq!(inner!(p, if let Ok((n, c)) = p), (1, 'a'));
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Fruit::Orange(1);
+            inner!(if let Fruit::Apple(w) = z => w);
+        }),
+        "Expected 'Fruit::Apple(w)' inside 'z' (type try_utils::if_let::Fruit), found Orange(1)"
+    );
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Fruit::Orange(1);
+            inner!(z, if let Fruit::Apple(w) = z);
+        }),
+        "Expected 'Fruit::Apple' inside 'z' (type try_utils::if_let::Fruit), found Orange(1)"
+    );
+}
+
+#[test]
+fn swap() {
+    #[derive(Debug, PartialEq)]
+    enum State {
+        Running(Vec<u8>),
+        Paused(Vec<u8>),
+        Stopped,
+    }
+
+    let state = State::Running(vec![1, 2]);
+    let state = swap_variant!(state, State::Running => State::Paused);
+    assert_eq!(state, State::Paused(vec![1, 2]));
+    let state = swap_variant!(state, State::Paused => State::Running, else panic!());
+    assert_eq!(state, State::Running(vec![1, 2]));
+
+    let state = swap_variant!(state, State::Paused => State::Running, else |e| {
+        assert_eq!(e, State::Running(vec![1, 2]));
+        State::Stopped
+    });
+    assert_eq!(state, State::Stopped);
+    assert_eq!(
+        swap_variant!(state, State::Running => State::Paused),
+        State::Stopped
+    );
+}
+
+#[test]
+fn ok_or_fmt() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let x = Fruit::Orange(5);
+    let r = ok!(x, if Fruit::Apple, or_fmt "expected Apple, got {:?}", x);
+    assert_eq!(r, Err("expected Apple, got Orange(5)".to_string()));
+
+    let x = Fruit::Apple(2);
+    let r = ok!(x, if Fruit::Apple, or_fmt "expected Apple");
+    assert_eq!(r, Ok(2));
+}
+
+#[test]
+fn rc_weak_upgrade() {
+    use std::cell::RefCell;
+    use std::rc::{Rc, Weak};
+
+    struct Node {
+        parent: Weak<RefCell<Vec<i32>>>,
+    }
+
+    fn push(node: &Node, v: i32) -> bool {
+        let parent = inner!(node.parent.upgrade(), else { return false });
+        parent.borrow_mut().push(v);
+        true
+    }
+
+    let parent = Rc::new(RefCell::new(vec![]));
+    let node = Node {
+        parent: Rc::downgrade(&parent),
+    };
+
+    let upgraded: Rc<RefCell<Vec<i32>>> = inner!(node.parent.upgrade(), else panic!());
+    assert_eq!(Rc::strong_count(&parent), 2);
+    upgraded.borrow_mut().push(1);
+    drop(upgraded);
+    assert_eq!(Rc::strong_count(&parent), 1);
+
+    assert!(push(&node, 2));
+    assert_eq!(*parent.borrow(), vec![1, 2]);
+
+    drop(parent);
+    assert!(!push(&node, 3));
+}
+
+#[test]
+fn arc_weak_upgrade() {
+    use std::sync::{Arc, Mutex, Weak};
+
+    struct Node {
+        parent: Weak<Mutex<Vec<i32>>>,
+    }
+
+    fn push(node: &Node, v: i32) -> bool {
+        let parent = inner!(node.parent.upgrade(), else return false);
+        parent.lock().unwrap().push(v);
+        true
+    }
+
+    let parent = Arc::new(Mutex::new(vec![]));
+    let node = Node {
+        parent: Arc::downgrade(&parent),
+    };
+
+    let upgraded: Arc<Mutex<Vec<i32>>> = inner!(node.parent.upgrade(), else panic!());
+    assert_eq!(Arc::strong_count(&parent), 2);
+    upgraded.lock().unwrap().push(1);
+    drop(upgraded);
+    assert_eq!(Arc::strong_count(&parent), 1);
+
+    assert!(push(&node, 2));
+    assert_eq!(*parent.lock().unwrap(), vec![1, 2]);
+
+    drop(parent);
+    assert!(!push(&node, 3));
+}
+
+#[test]
+fn match_any() {
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(&'static str),
+        Orange(char),
+        Pear(u8),
+    }
+
+    fn name(f: Fruit) -> String {
+        match_any_inner!(f, { Fruit::Apple, Fruit::Orange } into String, else |e| format!("{:?}", e))
+    }
+
+    assert_eq!(name(Fruit::Apple("granny smith")), "granny smith");
+    assert_eq!(name(Fruit::Orange('o')), "o");
+    assert_eq!(name(Fruit::Pear(1)), "Pear(1)");
+
+    let f = Fruit::Orange('x');
+    let s: String = match_any_inner!(f, { Fruit::Apple, Fruit::Orange } into String);
+    assert_eq!(s, "x");
+
+    let f = Fruit::Pear(2);
+    let n = match_any_inner!(f, { Fruit::Pear } into u32, else 0);
+    assert_eq!(n, 2);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let f = Fruit::Pear(3);
+            match_any_inner!(f, { Fruit::Apple, Fruit::Orange } into String);
+        }),
+        "Expected 'Fruit::Apple | Fruit::Orange' inside 'f' (type try_utils::match_any::Fruit), found Pear(3)"
+    );
+}
+
+#[test]
+fn guard() {
+    #[derive(Debug, PartialEq)]
+    struct Apple {
+        freshness: i32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Fruit {
+        Apple(Apple),
+        Orange(i16),
+    }
+
+    let z = Fruit::Apple(Apple { freshness: 3 });
+    let a = inner!(z, if Fruit::Apple, guard |a| a.freshness > 0, else panic!());
+    assert_eq!(a.freshness, 3);
+
+    let z = Fruit::Apple(Apple { freshness: 0 });
+    let a = inner!(z, if Fruit::Apple, guard |a| a.freshness > 0, else |e| {
+        assert_eq!(e, Fruit::Apple(Apple { freshness: 0 }));
+        Apple { freshness: -1 }
+    });
+    assert_eq!(a.freshness, -1);
+
+    let z = Fruit::Orange(1);
+    let a = inner!(z, if Fruit::Apple, guard |a| a.freshness > 0, else Apple { freshness: -2 });
+    assert_eq!(a.freshness, -2);
+
+    let mut checked = 0;
+    let z = Fruit::Apple(Apple { freshness: 1 });
+    let a = inner!(z, if Fruit::Apple, guard |_a| { checked += 1; checked > 1 }, else Apple { freshness: -3 });
+    assert_eq!((a.freshness, checked), (-3, 1));
+
+    let z = Fruit::Orange(4);
+    let n = inner!(z, if Fruit::Orange, guard |n| n > 2);
+    assert_eq!(n, 4);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Fruit::Orange(1);
+            inner!(z, if Fruit::Orange, guard |n| n > 2);
+        }),
+        "Expected 'Fruit::Orange (guard)' inside 'z' (type try_utils::guard::Fruit), found Orange(1)"
+    );
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Fruit::Apple(Apple { freshness: 1 });
+            inner!(z, if Fruit::Orange, guard |n| n > 2);
+        }),
+        "Expected 'Fruit::Orange' inside 'z' (type try_utils::guard::Fruit), found Apple(Apple { freshness: 1 })"
+    );
+
+    assert_eq!(
+        some!(Fruit::Orange(4), if Fruit::Orange, guard |n| n > 2),
+        Some(4)
+    );
+    assert_eq!(
+        some!(Fruit::Orange(1), if Fruit::Orange, guard |n| n > 2),
+        None
+    );
+    assert_eq!(
+        ok!(Fruit::Orange(4), if Fruit::Orange, guard |n| n > 2),
+        Ok(4)
+    );
+    assert_eq!(
+        ok!(Fruit::Orange(1), if Fruit::Orange, guard |n| n > 2),
+        Err(Fruit::Orange(1))
+    );
+    assert_eq!(
+        ok!(Fruit::Apple(Apple { freshness: 1 }), if Fruit::Orange, guard |n| n > 2),
+        Err(Fruit::Apple(Apple { freshness: 1 }))
+    );
+}
+
+// Also run under Miri in CI, to check that the valid path is sound.
+#[test]
+fn debug_checked() {
+    #[allow(dead_code)]
+    enum Fruit {
+        Apple(String),
+        Orange(i16),
+    }
+
+    let z = Fruit::Apple("red".to_string());
+    // SAFETY: `z` is an apple.
+    let a = unsafe { inner_debug_checked!(z, if Fruit::Apple) };
+    assert_eq!(a, "red");
+
+    let z = Fruit::Orange(2);
+    // SAFETY: `&z` is a reference to an orange.
+    let o: &i16 = unsafe { inner_debug_checked!(&z, if Fruit::Orange) };
+    assert_eq!(*o, 2);
+
+    let x: Option<Box<u8>> = Some(Box::new(4));
+    // SAFETY: `x` is `Some`.
+    assert_eq!(*unsafe { inner_debug_checked!(x) }, 4);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "Expected 'Some' inside 'x'")]
+fn debug_checked_fail() {
+    let x: Option<u8> = None;
+    // Not actually safe, but this only runs with `debug_assertions`, where
+    // it panics.
+    let _ = unsafe { inner_debug_checked!(x, if Some) };
+}
+
+#[test]
+fn err_with() {
+    #[derive(Debug, PartialEq)]
+    enum MyErr {
+        Missing,
+    }
+
+    fn get(x: Option<i32>) -> Result<i32, MyErr> {
+        let v = inner!(x, err_with || MyErr::Missing, else |e| return Err(e));
+        Ok(v)
+    }
+
+    assert_eq!(get(Some(1)), Ok(1));
+    assert_eq!(get(None), Err(MyErr::Missing));
+
+    let mut called = false;
+    let v = inner!(Some(2), err_with || { called = true; MyErr::Missing }, else |_e| 0);
+    assert_eq!(v, 2);
+    assert!(!called);
+}
+
+// rustfmt would space out the closure-like clauses, `|v|` into `| v |`.
+#[rustfmt::skip::macros(inner)]
+#[test]
+fn filter() {
+    assert_eq!(inner!(Some(3), filter |v| v > 0, else 0), 3);
+    assert_eq!(inner!(Some(-3), filter |v| v > 0, else 0), 0);
+    assert_eq!(inner!(None::<i32>, filter |v| v > 0, else 0), 0);
+
+    let names = Some("".to_string());
+    let name = inner!(names, filter |s| !s.is_empty(), else |e| {
+        assert_eq!(e, Some(String::new()));
+        "anonymous".to_string()
+    });
+    assert_eq!(name, "anonymous");
+
+    assert_eq!(inner!(Some(5), filter |v| v % 5 == 0), 5);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let x = Some(4);
+            inner!(x, filter |v| v % 5 == 0);
+        }),
+        "Expected 'Some' inside 'x' (type core::option::Option<i32>), found Some(4)"
+    );
+}
+
+#[test]
+fn inner_test() {
+    #[allow(dead_code)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let mut seen = Vec::new();
+    assert!(inner_test!(Fruit::Apple(3), if Fruit::Apple, |n| seen.push(n)));
+    assert!(!inner_test!(Fruit::Orange(4), if Fruit::Apple, |n| seen.push(n)));
+    assert_eq!(seen, [3]);
+
+    let fruits = [Fruit::Orange(1), Fruit::Apple(5), Fruit::Apple(6)];
+    let mut total = 0;
+    let apples = fruits
+        .iter()
+        .filter(|f| inner_test!(f, if Fruit::Apple, |n: &i32| total += *n))
+        .count();
+    assert_eq!((apples, total), (2, 11));
+
+    let mut runs = 0;
+    assert!(inner_test!(Some(1), |_| runs += 1));
+    assert!(!inner_test!(None::<i32>, |_| runs += 1));
+    let r: Result<i32, ()> = Err(());
+    assert!(!inner_test!(r, |_| runs += 1));
+    assert_eq!(runs, 1);
+}
+
+#[test]
+fn btree_map_get() {
+    use std::collections::BTreeMap;
+
+    let mut names = BTreeMap::new();
+    names.insert(1, "one".to_string());
+    let unknown = "unknown".to_string();
+
+    assert_eq!(inner!(names.get(&1), else &unknown), "one");
+    assert_eq!(inner!(names.get(&2), else &unknown), "unknown");
+    assert_eq!(inner!(names.get(&1)).len(), 3);
+
+    *inner!(names.get_mut(&1), else return) += "!";
+    assert_eq!(names[&1], "one!");
+}
+
+#[test]
+fn any_inner() {
+    use std::any::Any;
+
+    #[derive(Debug, PartialEq)]
+    struct Plugin {
+        name: &'static str,
+    }
+
+    let boxed: Box<dyn Any> = Box::new(Plugin { name: "echo" });
+    assert_eq!(
+        any_inner!(boxed, as Plugin, else return),
+        Plugin { name: "echo" }
+    );
+
+    let boxed: Box<dyn Any> = Box::new(7u8);
+    assert_eq!(any_inner!(boxed, as u16, else 0), 0);
+
+    let payload = std::panic::catch_unwind(|| panic!("{}", "boom")).unwrap_err();
+    assert_eq!(any_inner!(payload, as String, else String::new()), "boom");
+
+    let boxed: Box<dyn Any> = Box::new(7u8);
+    any_inner!(boxed, as Plugin, else |e| {
+        assert_eq!(any_inner!(e, as u8, else 0), 7);
+        return;
+    });
+    panic!("a u8 was downcast to a Plugin");
+}
+
+#[test]
+fn ok_flat() {
+    #[derive(Debug, PartialEq)]
+    enum ParseError {
+        Invalid(String),
+        NotParsed,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Field {
+        Parsed(Result<u8, ParseError>),
+        Raw(&'static str),
+    }
+
+    impl From<Field> for ParseError {
+        fn from(_: Field) -> Self {
+            ParseError::NotParsed
+        }
+    }
+
+    let r: Result<u8, ParseError> = ok_flat!(Field::Parsed(Ok(3)), if Field::Parsed);
+    assert_eq!(r, Ok(3));
+    let invalid = Field::Parsed(Err(ParseError::Invalid("x".to_string())));
+    assert_eq!(
+        ok_flat!(invalid, if Field::Parsed),
+        Err(ParseError::Invalid("x".to_string()))
+    );
+    assert_eq!(
+        ok_flat!(Field::Raw("4"), if Field::Parsed),
+        Err(ParseError::NotParsed)
+    );
+
+    let raw = ok_flat!(Field::Raw("4"), if Field::Parsed, or |e| {
+        ParseError::Invalid(format!("{:?}", e))
+    });
+    assert_eq!(raw, Err(ParseError::Invalid("Raw(\"4\")".to_string())));
+
+    // The payload goes through `IntoResult`, so an `Option` works too.
+    enum Slot {
+        Filled(Option<i32>),
+        _Locked,
+    }
+    assert_eq!(
+        ok_flat!(Slot::Filled(None), if Slot::Filled, or ()),
+        Err(())
+    );
+}
+
+#[test]
+fn maybe_uninit() {
+    use std::rc::Rc;
+
+    let value = Rc::new(5);
+
+    let slot = SafeMaybeUninit::new(Rc::clone(&value));
+    assert_eq!(*inner!(slot), 5);
+    assert_eq!(Rc::strong_count(&value), 1);
+
+    let slot = SafeMaybeUninit::<Rc<i32>>::uninit();
+    assert!(!slot.is_init());
+    assert_eq!(*inner!(slot, else Rc::new(0)), 0);
+
+    let mut slot = SafeMaybeUninit::uninit();
+    slot.write(Rc::clone(&value));
+    slot.write(Rc::clone(&value));
+    assert_eq!(Rc::strong_count(&value), 2);
+    drop(slot);
+    assert_eq!(Rc::strong_count(&value), 1);
+}
+
+#[test]
+fn deref_inner() {
+    #[derive(Debug, PartialEq)]
+    struct Config {
+        name: String,
+    }
+
+    let boxed = Some(Box::new(Config {
+        name: "a".to_string(),
+    }));
+    let config: Config = deref_inner!(boxed, else return);
+    assert_eq!(config.name, "a");
+
+    let missing: Option<Box<Config>> = None;
+    let fallback = deref_inner!(missing, else Config { name: "default".to_string() });
+    assert_eq!(fallback.name, "default");
+
+    let r: Result<Box<i32>, &str> = Err("gone");
+    assert_eq!(deref_inner!(r, else |e| e.len() as i32), 4);
+    assert_eq!(deref_inner!(Some(Box::new(7))), 7);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let b: Option<Box<i32>> = None;
+            let _ = deref_inner!(b);
+        }),
+        "Unexpected value found inside 'b' (type core::option::Option<alloc::boxed::Box<i32>>)"
+    );
+}
+
+// The payload arm comes first and settles the type of the match, so a
+// diverging `else` infers in these positions without any help.
+#[test]
+fn divergent_else_positions() {
+    struct Parsed {
+        name: String,
+        len: usize,
+    }
+
+    fn widen<T: Into<u64>>(t: T) -> u64 {
+        t.into()
+    }
+
+    fn id<T>(t: T) -> T {
+        t
+    }
+
+    struct Boxed<T> {
+        value: T,
+    }
+
+    fn run(n: Option<u32>, name: Option<String>, r: Result<String, ()>) -> Option<u64> {
+        // Argument positions, plain and generic.
+        let a = u64::from(inner!(n, else return None));
+        let b = widen(inner!(n, else return None));
+        // Nested generic calls, with the type left to inference.
+        let e = widen::<_>(id(inner!(n, if Some, else return None)));
+        let f: u64 = Into::into(id(id(inner!(n, else |_e| return None))));
+        // Struct literal fields, plain and generic.
+        let parsed = Parsed {
+            name: inner!(name.clone(), else return None),
+            len: inner!(r.clone(), else |_e| return None).len(),
+        };
+        let boxed = Boxed {
+            value: id(inner!(r.clone(), if Ok, else |_e| return None)),
+        };
+        // Method receivers.
+        let c = inner!(name, else return None).chars().count();
+        let d = inner!(r, else |_e| return None).len();
+        let g = id(inner!(n, else return None)).count_ones();
+        let total = [
+            a,
+            b,
+            e,
+            f,
+            widen(parsed.len as u32),
+            c as u64,
+            d as u64,
+            boxed.value.len() as u64,
+            g.into(),
+        ];
+        Some(total.iter().sum::<u64>() + parsed.name.len() as u64)
+    }
+
+    // A borrow of a temporary in the value lives as long as the statement.
+    fn first(m: &std::sync::Mutex<Vec<u32>>) -> Option<u64> {
+        Some(widen(*inner!(m.lock().unwrap().first(), else return None)))
+    }
+
+    assert_eq!(
+        run(Some(1), Some("ab".to_string()), Ok("xyz".to_string())),
+        Some(18)
+    );
+    assert_eq!(
+        run(None, Some("ab".to_string()), Ok("xyz".to_string())),
+        None
+    );
+    assert_eq!(run(Some(1), Some("ab".to_string()), Err(())), None);
+    assert_eq!(first(&std::sync::Mutex::new(vec![4])), Some(4));
+    assert_eq!(first(&std::sync::Mutex::new(vec![])), None);
+}
+
+#[test]
+fn type_alias_variants() {
+    #[derive(Debug, PartialEq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+    type F = Fruit;
+
+    assert_eq!(inner!(F::Apple(3), if F::Apple), 3);
+    assert_eq!(inner!(Fruit::Orange(1), if F::Apple, else -1), -1);
+    assert_eq!(some!(F::Orange(2), if F::Orange), Some(2));
+    assert_eq!(ok!(F::Orange(2), if F::Apple), Err(Fruit::Orange(2)));
+    assert_eq!(inner!(F::Apple(4), if F::Apple, guard |n| n > 0, else 0), 4);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            inner!(Fruit::Orange(5), if F::Apple);
+        }),
+        "Expected 'F::Apple' inside 'Fruit::Orange(5)' (type try_utils::type_alias_variants::Fruit), found Orange(5)"
+    );
+}
+
+#[test]
+fn infallible() {
+    use std::convert::Infallible;
+
+    let r: Result<i32, Infallible> = Ok(3);
+    assert_eq!(inner!(r), 3);
+    let r: Result<i32, Infallible> = Ok(4);
+    assert_eq!(inner!(r, else |e| match e {}), 4);
+    assert_eq!(inner!("5".parse::<String>(), else |e| match e {}), "5");
+    assert_eq!(some!(Ok::<i32, Infallible>(6)), Some(6));
+}
+
+#[test]
+fn inner_cow() {
+    use std::borrow::Cow;
+    use std::path::{Path, PathBuf};
+
+    #[derive(Debug)]
+    enum Msg {
+        Text(String),
+        Static(&'static str),
+        Bytes(Vec<u8>),
+        File(PathBuf),
+        Ping,
+    }
+
+    let msg = Msg::Text("hi".to_string());
+    let text: Cow<str> = inner_cow!(&msg, if Msg::Text);
+    assert!(matches!(text, Cow::Borrowed("hi")));
+
+    let text: Cow<str> = inner_cow!(&Msg::Static("static"), if Msg::Static);
+    assert!(matches!(text, Cow::Borrowed("static")));
+
+    let data = Msg::Bytes(vec![1, 2]);
+    let bytes: Cow<[u8]> = inner_cow!(&data, if Msg::Bytes);
+    assert!(matches!(bytes, Cow::Borrowed(&[1, 2])));
+
+    let file = Msg::File(PathBuf::from("a.txt"));
+    let path: Cow<Path> = inner_cow!(&file, if Msg::File);
+    assert_eq!(path, Cow::Borrowed(Path::new("a.txt")));
+
+    let text: Cow<str> = inner_cow!(&Msg::Ping, if Msg::Text, else "none");
+    assert!(matches!(text, Cow::Borrowed("none")));
+    let text: Cow<str> = inner_cow!(&Msg::Ping, if Msg::Text, else String::from("owned"));
+    assert!(matches!(text, Cow::Owned(ref s) if s == "owned"));
+
+    #[cfg(not(feature = "defmt"))]
+    assert!(panic_message(|| {
+        let _: Cow<str> = inner_cow!(&Msg::Ping, if Msg::Text);
+    })
+    .starts_with("Expected 'Msg::Text' inside '&Msg::Ping'"));
+}
+
+#[test]
+fn projection() {
+    #[derive(Debug, PartialEq)]
+    struct Pos {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Key {
+        code: u32,
+        pos: Pos,
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Key(Key),
+        Pair((u8, (u8, u8))),
+        Quit,
+    }
+
+    let ev = Event::Key(Key {
+        code: 13,
+        pos: Pos { x: 1, y: 2 },
+        name: "enter".to_string(),
+    });
+
+    assert_eq!(inner!(&ev, if Event::Key, .code), 13);
+    assert_eq!(inner!(&ev, if Event::Key, .pos.y), 2);
+    let name: &String = inner!(&ev, if Event::Key, ref .name);
+    assert_eq!(name, "enter");
+    let pos: &Pos = inner!(&ev, if Event::Key, ref .pos, else return);
+    assert_eq!(pos, &Pos { x: 1, y: 2 });
+
+    let pair = Event::Pair((1, (2, 3)));
+    assert_eq!(inner!(&pair, if Event::Pair, .1.0), 2);
+    assert_eq!(inner!(&pair, if Event::Pair, .0, else 0), 1);
+
+    let code = inner!(Event::Quit, if Event::Key, .code, else |e| {
+        assert_eq!(e, Event::Quit);
+        0
+    });
+    assert_eq!(code, 0);
+    let name: String = inner!(ev, if Event::Key, .name, else String::new());
+    assert_eq!(name, "enter");
+
+    #[cfg(not(feature = "defmt"))]
+    assert!(panic_message(|| {
+        inner!(Event::Quit, if Event::Key, .code);
+    })
+    .starts_with("Expected 'Event::Key' inside 'Event::Quit'"));
+}
+
+#[test]
+fn linked_list_front() {
+    use std::collections::LinkedList;
+
+    let mut queue: LinkedList<String> = LinkedList::new();
+    let empty = String::new();
+    assert_eq!(inner!(queue.front(), else &empty).len(), 0);
+
+    queue.push_back("b".to_string());
+    let first = inner!(queue.front());
+    assert_eq!(first, "b");
+    // The borrow ends with the last use of `first`, not with the macro.
+    queue.push_front("a".to_string());
+    inner!(queue.front_mut()).push('!');
+    let first = inner!(queue.front(), else return);
+    assert_eq!(first, "a!");
+    queue.pop_front();
+    assert_eq!(inner!(queue.back()), "b");
+}
+
+#[test]
+fn inner_first() {
+    #[derive(Debug, PartialEq)]
+    enum Fruit {
+        Apple(i32),
+        Pear(u8),
+        Orange(&'static str),
+        Rotten,
+    }
+
+    fn weight(f: Fruit) -> i64 {
+        inner_first!(f, [Fruit::Apple, Fruit::Pear, Fruit::Orange => |s: &str| s.len() as i32] into i64, else 0)
+    }
+
+    assert_eq!(weight(Fruit::Apple(3)), 3);
+    assert_eq!(weight(Fruit::Pear(4)), 4);
+    assert_eq!(weight(Fruit::Orange("seven")), 5);
+    assert_eq!(weight(Fruit::Rotten), 0);
+
+    let name = inner_first!(Fruit::Orange("blood"), [
+        Fruit::Apple => |_| "apple",
+        Fruit::Pear => |_| "pear",
+        Fruit::Orange,
+    ], else |e| {
+        assert_eq!(e, Fruit::Rotten);
+        "rotten"
+    });
+    assert_eq!(name, "blood");
+    assert_eq!(
+        inner_first!(Fruit::Rotten, [Fruit::Orange], else "none"),
+        "none"
+    );
+}
+
+#[test]
+fn else_some() {
+    #[derive(Debug, PartialEq)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i32),
+    }
+
+    assert_eq!(
+        some!(Fruit::Apple(3), if Fruit::Apple, else_some 0),
+        Some(3)
+    );
+    assert_eq!(
+        some!(Fruit::Orange(5), if Fruit::Apple, else_some 0),
+        Some(0)
+    );
+    let doubled = some!(Fruit::Orange(5), if Fruit::Apple, else_some |e| match e {
+        Fruit::Orange(n) => n * 2,
+        Fruit::Apple(_) => unreachable!(),
+    });
+    assert_eq!(doubled, Some(10));
+
+    let mut seen = Vec::new();
+    for f in [Fruit::Apple(1), Fruit::Orange(2), Fruit::Apple(3)] {
+        let apple = some!(f, if Fruit::Apple, else_some continue);
+        seen.push(apple);
+    }
+    assert_eq!(seen, [Some(1), Some(3)]);
+}
+
+#[test]
+fn turbofish_subjects() {
+    use std::collections::HashSet;
+
+    assert_eq!(inner!(Vec::<i32>::new().pop(), else 0), 0);
+    assert_eq!(inner!(vec![1].pop(), else |_e| 0), 1);
+    assert_eq!(inner!(Vec::<i32>::from([4]).pop()), 4);
+    assert_eq!(inner!("7".parse::<u8>(), else |e| panic!("{}", e)), 7);
+    assert_eq!(
+        inner!(Result::<i32, ()>::Ok(2), if Result::<i32, ()>::Ok),
+        2
+    );
+    assert_eq!(inner!(Option::<u8>::None, if Option::<u8>::Some, else 9), 9);
+    assert_eq!(
+        inner!(if let Some(v) = Vec::<i32>::new().pop() => v, else -1),
+        -1
+    );
+    assert_eq!(
+        inner!(Some(3u8), if Some, guard |n| n > u8::from(false), else 0),
+        3
+    );
+    assert_eq!(some!(Vec::<u8>::new().pop()), None);
+    assert_eq!(some!(Option::<u8>::Some(1), if Option::<u8>::Some), Some(1));
+    assert_eq!(
+        ok!(Option::<u8>::None, if Option::<u8>::Some, or "none"),
+        Err("none")
+    );
+    assert_eq!(first_inner!(Vec::<u8>::new(), else 5), 5);
+    assert!(inner_test!(
+        HashSet::<i32>::new().insert(1).then_some(()),
+        |_| ()
+    ));
+
+    #[cfg(not(feature = "defmt"))]
+    assert!(panic_message(|| {
+        inner!(Vec::<i32>::new().pop());
+    })
+    .starts_with("Unexpected value found inside 'Vec::<i32>::new().pop()'"));
+}
+
+#[test]
+fn buf_read_lines() {
+    use std::io::{BufRead, BufReader, ErrorKind};
+
+    let input: &[u8] = b"a\nb\n\xff\nc\n";
+    let mut lines = Vec::new();
+    let mut error = None;
+    for line in BufReader::new(input).lines() {
+        let s = inner!(line, else |e| {
+            error = Some(e.kind());
+            break;
+        });
+        lines.push(s);
+    }
+    assert_eq!(lines, ["a", "b"]);
+    assert_eq!(error, Some(ErrorKind::InvalidData));
+
+    let mut skipped = 0;
+    let mut lengths = Vec::new();
+    for line in input.lines() {
+        let s = inner!(line, else |_e| {
+            skipped += 1;
+            continue;
+        });
+        lengths.push(s.len());
+    }
+    assert_eq!((lengths, skipped), (vec![1, 1, 1], 1));
+}
+
+#[test]
+fn transpose_variant() {
+    #[derive(Debug, PartialEq)]
+    enum Response {
+        Cached(Option<String>),
+        Fresh(String),
+        Miss,
+    }
+
+    let data = || Some("data".to_string());
+
+    // The four ways in: variant or not, payload or not.
+    assert_eq!(
+        transpose_variant!(Response::Cached(data()), if Response::Cached),
+        data()
+    );
+    assert_eq!(
+        transpose_variant!(Response::Cached(None), if Response::Cached),
+        None
+    );
+    assert_eq!(
+        transpose_variant!(Response::Miss, if Response::Cached),
+        None
+    );
+    let fresh = transpose_variant!(Response::Fresh("new".to_string()), if Response::Cached, else |e| match e {
+        Response::Fresh(s) => Some(s),
+        _ => None,
+    });
+    assert_eq!(fresh, Some("new".to_string()));
+
+    // An empty payload does not reach the else clause.
+    let mut fell_back = false;
+    let r = transpose_variant!(Response::Cached(None), if Response::Cached, else {
+        fell_back = true;
+        None
+    });
+    assert_eq!((r, fell_back), (None, false));
+
+    // And back.
+    assert_eq!(
+        transpose_variant!(data(), into Response::Cached),
+        Response::Cached(data())
+    );
+    assert_eq!(
+        transpose_variant!(None, into Response::Cached),
+        Response::Cached(None)
+    );
+    assert_eq!(
+        transpose_variant!(data(), into Response::Cached, or Response::Miss),
+        Response::Cached(data())
+    );
+    assert_eq!(
+        transpose_variant!(None, into Response::Cached, or Response::Miss),
+        Response::Miss
+    );
+}
+
+#[test]
+fn extract_or_continue_break() {
+    #[derive(Clone, Copy)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let basket = vec![
+        Fruit::Apple(1),
+        Fruit::Orange(2),
+        Fruit::Apple(3),
+        Fruit::Orange(4),
+        Fruit::Apple(5),
+    ];
+
+    let mut apples = Vec::new();
+    for &f in &basket {
+        apples.push(extract_or_continue!(f, if Fruit::Apple));
+    }
+    assert_eq!(apples, [1, 3, 5]);
+
+    let mut leading = Vec::new();
+    for &f in &basket {
+        leading.push(extract_or_break!(f, if Fruit::Apple));
+    }
+    assert_eq!(leading, [1]);
+
+    let mut oranges = Vec::new();
+    for f in basket {
+        oranges.push(extract_or_continue!(f, if Fruit::Orange));
+    }
+    assert_eq!(oranges, [2, 4]);
+
+    let mut some = Vec::new();
+    for v in [Some(1), None, Some(3)] {
+        some.push(extract_or_continue!(v));
+    }
+    assert_eq!(some, [1, 3]);
+
+    let mut ok = Vec::new();
+    for v in [Ok(1), Err(()), Ok(3)] {
+        ok.push(extract_or_break!(v));
+    }
+    assert_eq!(ok, [1]);
+}
+
+#[test]
+fn select_variant() {
+    #[derive(Debug, PartialEq)]
+    enum Node {
+        Text(String),
+        Comment(String),
+        Width(u32),
+    }
+
+    fn texts(nodes: &[Node]) -> Vec<&String> {
+        select_variant!(nodes, if Node::Text)
+    }
+
+    let mut nodes = vec![
+        Node::Text("a".to_string()),
+        Node::Width(3),
+        Node::Comment("bb".to_string()),
+        Node::Text("ccc".to_string()),
+        Node::Width(0),
+    ];
+
+    assert_eq!(texts(&nodes), ["a", "ccc"]);
+    assert_eq!(
+        select_variant!(&nodes, if Node::Text | Node::Comment),
+        ["a", "bb", "ccc"]
+    );
+    assert_eq!(
+        select_variant!(&nodes, if Node::Text | Node::Comment, guard |s| s.len() > 1),
+        ["bb", "ccc"]
+    );
+    assert_eq!(
+        select_variant!(&nodes[1..], if Node::Width, guard |n| *n > 0),
+        [&3]
+    );
+    assert!(select_variant!(&nodes[..1], if Node::Width).is_empty());
+
+    for n in select_variant_mut!(&mut nodes, if Node::Width) {
+        *n += 1;
+    }
+    for s in select_variant_mut!(&mut nodes, if Node::Text | Node::Comment, guard |s| s.len() < 3) {
+        s.push('!');
+    }
+    assert_eq!(
+        nodes,
+        [
+            Node::Text("a!".to_string()),
+            Node::Width(4),
+            Node::Comment("bb!".to_string()),
+            Node::Text("ccc".to_string()),
+            Node::Width(1),
+        ]
+    );
+}
+
+#[test]
+fn scan_inner() {
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+        Pear(String),
+    }
+
+    let basket = [
+        Fruit::Orange(1),
+        Fruit::Pear("p".to_string()),
+        Fruit::Apple(7),
+        Fruit::Apple(8),
+    ];
+    assert_eq!(scan_inner!(ref basket, if Fruit::Apple), Some(&7));
+    assert_eq!(scan_inner!(ref basket[..2], if Fruit::Apple), None);
+    assert_eq!(scan_inner!(ref basket[3..], if Fruit::Apple), Some(&8));
+    assert_eq!(scan_inner!(ref basket, if Fruit::Orange), Some(&1));
+
+    let empty: [Fruit; 0] = [];
+    assert_eq!(scan_inner!(empty, if Fruit::Apple), None);
+
+    fn first_apple(fruits: &[Fruit]) -> Option<&i32> {
+        scan_inner!(ref fruits, if Fruit::Apple)
+    }
+    assert_eq!(first_apple(&basket), Some(&7));
+    assert_eq!(first_apple(&basket[..2]), None);
+    let mut bag = vec![Fruit::Orange(2)];
+    bag.push(Fruit::Apple(9));
+    assert_eq!(scan_inner!(ref bag, if Fruit::Apple), Some(&9));
+
+    let pear: Option<String> = scan_inner!(basket, if Fruit::Pear);
+    assert_eq!(pear.as_deref(), Some("p"));
+}
+
+#[test]
+fn const_inner() {
+    enum Entry {
+        Timeout(u64),
+        Retries(u32),
+        Name(&'static str),
+    }
+
+    const DEFAULTS: &[Entry] = &[Entry::Timeout(30), Entry::Retries(3), Entry::Name("fruit")];
+    const DEFAULT_TIMEOUT: u64 = const_inner!(DEFAULTS[0], if Entry::Timeout);
+    const DEFAULT_RETRIES: u32 = const_inner!(DEFAULTS[1], if Entry::Retries);
+    static NAME: &str = const_inner!(DEFAULTS[2], if Entry::Name);
+    const FIXED: u64 = const_inner!(Entry::Timeout(5), if Entry::Timeout);
+
+    assert_eq!(DEFAULT_TIMEOUT, 30);
+    assert_eq!(DEFAULT_RETRIES, 3);
+    assert_eq!(NAME, "fruit");
+    assert_eq!(FIXED, 5);
+
+    // In a function body, the match is still done at compile time.
+    let retries = const_inner!(DEFAULTS[1], if Entry::Retries);
+    assert_eq!(retries, 3);
+}
+
+// Kept from rustfmt for the same reason as `filter`.
+#[rustfmt::skip::macros(inner)]
+#[test]
+fn then_clause() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(String),
+        Orange(i16),
+    }
+
+    let name: Option<&str> = Some(" granny smith ");
+    assert_eq!(
+        inner!(name, then |v| v.trim().to_owned(), else String::new()),
+        "granny smith"
+    );
+    let name: Option<&str> = None;
+    assert_eq!(
+        inner!(name, then |v| v.trim().to_owned(), else String::new()),
+        ""
+    );
+
+    let r: Result<i32, String> = Err("bad".to_string());
+    assert_eq!(inner!(r, then |n| n.to_string(), else |e| e), "bad");
+    assert_eq!(inner!(Some(4), then |n| n * n), 16);
+
+    // Temporaries in the value live as long as with the `else` forms.
+    let cell = std::cell::RefCell::new(vec!["kiwi".to_string()]);
+    assert_eq!(inner!(cell.borrow().first(), then |v| v.len()), 4);
+    assert_eq!(inner!(cell.borrow().first(), then |v| v.len(), else 0), 4);
+
+    let z = Fruit::Apple("Green".to_string());
+    assert_eq!(
+        inner!(z, if Fruit::Apple, then |a| a.to_lowercase()),
+        "green"
+    );
+    let z = Fruit::Orange(3);
+    assert_eq!(inner!(z, if Fruit::Apple, then |a| a.len(), else 0), 0);
+    let z = Fruit::Orange(3);
+    assert_eq!(
+        inner!(z, if Fruit::Apple, then |a| a, else |e| format!("{:?}", e)),
+        "Orange(3)"
+    );
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Fruit::Orange(1);
+            inner!(z, if Fruit::Apple, then |a| a.len());
+        }),
+        "Expected 'Fruit::Apple' inside 'z' (type try_utils::then_clause::Fruit), found Orange(1)"
+    );
+}
 
-    ($x:expr, if $i:path, or |$e:ident| $b:expr) => {{
-        match $x {
-            $i(q) => Ok(q),
-            $e @ _ => Err($b),
-        }
-    }};
+#[test]
+fn ok_boxed_err() {
+    #[allow(clippy::large_enum_variant)]
+    #[derive(Debug, PartialEq)]
+    enum Packet {
+        Ack(u8),
+        Data([u8; 256]),
+    }
 
-    ($x:expr, if $i:path, or $b:expr) => {{
-        match $x {
-            $i(q) => Ok(q),
-            _ => Err($b),
-        }
-    }};
+    let r: Result<u8, Box<Packet>> = ok!(Packet::Ack(3), if Packet::Ack, boxed_err);
+    assert_eq!(r, Ok(3));
 
-    ($x:expr, if $i:path) => {{
-        match $x {
-            $i(q) => Ok(q),
-            n @ _ => Err(n),
-        }
-    }};
+    let r = ok!(Packet::Data([7; 256]), if Packet::Ack, boxed_err);
+    let whole: Box<Packet> = r.unwrap_err();
+    assert_eq!(*whole, Packet::Data([7; 256]));
+
+    assert!(core::mem::size_of::<Result<u8, Box<Packet>>>() <= 2 * core::mem::size_of::<usize>());
+    assert!(core::mem::size_of::<Result<u8, Packet>>() > 256);
 }
 
+#[cfg(feature = "std")]
 #[test]
-fn simple_opt() {
-    assert_eq!(inner!(Some(7)), 7);
+fn rich_option() {
+    use std::collections::HashMap;
+    use std::error::Error;
+
+    fn port(config: &HashMap<&str, &str>) -> Result<u16, Box<dyn Error>> {
+        let raw = inner!(RichOption(config.get("port")), else |e| return Err(e));
+        Ok(raw.parse()?)
+    }
+
+    let mut config = HashMap::new();
+    let err = port(&config).unwrap_err();
+    assert_eq!(err.to_string(), "expected a value, found None");
+    assert!(err.is::<MissingValue>());
+    config.insert("port", "808o");
+    assert!(port(&config).is_err());
+    config.insert("port", "8080");
+    assert_eq!(port(&config).unwrap(), 8080);
+
+    assert_eq!(RichOption(Some(3)).into_result().unwrap(), 3);
 }
 
+#[cfg(all(feature = "process", unix))]
 #[test]
-#[should_panic]
-fn simple_opt_fail() {
-    let z: Option<i32> = None;
-    inner!(z);
+fn process_output() {
+    use std::process::Command;
+
+    let sh = |script: &str| Command::new("sh").arg("-c").arg(script).output().unwrap();
+
+    assert_eq!(inner!(sh("echo apple")), b"apple\n");
+    let stderr = inner!(sh("echo rotten >&2; exit 3"), else |o| {
+        assert_eq!(o.status.code(), Some(3));
+        o.stderr
+    });
+    assert_eq!(stderr, b"rotten\n");
+
+    let status = sh("exit 4").status;
+    assert_eq!(status.into_result().unwrap_err().code(), Some(4));
 }
 
 #[test]
-fn else_clause() {
-    let x: Result<String, i32> = Err(7);
-    let _ = inner!(x, else return);
-    panic!();
+fn borrow_inner() {
+    use std::cell::{Ref, RefCell};
+    use std::rc::Rc;
+
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let cell = RefCell::new(Fruit::Apple(3));
+    let apple: Ref<i32> = borrow_inner!(cell, if Fruit::Apple);
+    assert_eq!(*apple, 3);
+    assert!(cell.try_borrow_mut().is_err());
+    drop(apple);
+
+    *cell.borrow_mut() = Fruit::Orange(2);
+    let whole = borrow_inner!(cell, if Fruit::Apple, else |e| {
+        assert!(matches!(*e, Fruit::Orange(2)));
+        Ref::map(e, |_| &0)
+    });
+    assert_eq!(*whole, 0);
+    drop(whole);
+
+    let shared = Rc::new(RefCell::new(Fruit::Orange(4)));
+    assert_eq!(*borrow_inner!(shared, if Fruit::Orange), 4);
+    assert_eq!(*borrow_inner!(&*shared, if Fruit::Orange, else return), 4);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = RefCell::new(Fruit::Orange(1));
+            borrow_inner!(z, if Fruit::Apple);
+        }),
+        "Expected 'Fruit::Apple' inside 'z' (type try_utils::borrow_inner::Fruit), found Orange(1)"
+    );
 }
 
+#[cfg(feature = "rayon")]
 #[test]
-fn else_clause_2() {
-    let x: Result<String, i32> = Err(7);
-    let y = inner!(x, else |e| {
-        assert_eq!(e, 7);
-        (e + 2).to_string()
-    });
-    assert_eq!(&y, "9");
+fn rayon() {
+    use rayon::prelude::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Record {
+        Valid(u32),
+        Invalid(String),
+    }
+
+    const N: u32 = 300_000;
+    let classify = |n: u32| {
+        if n % 7 == 0 {
+            Err(n.to_string())
+        } else {
+            Ok(n)
+        }
+    };
+
+    let results: Vec<Result<u32, String>> = (0..N).map(classify).collect();
+    let oks: Vec<u32> = results.iter().cloned().filter_map(Result::ok).collect();
+    let errs: Vec<String> = results.iter().cloned().filter_map(Result::err).collect();
+
+    let par_oks: Vec<u32> = results.clone().into_par_iter().par_oks().collect();
+    let par_errs: Vec<String> = results.clone().into_par_iter().par_errs().collect();
+    assert_eq!(par_oks, oks);
+    assert_eq!(par_errs, errs);
+
+    let (p_oks, p_errs) = (0..N)
+        .into_par_iter()
+        .map(classify)
+        .par_partition_variants();
+    assert_eq!(p_oks.len() + p_errs.len(), N as usize);
+    assert_eq!((p_oks, p_errs), (oks, errs));
+
+    let evens: Vec<u32> = (0..N)
+        .into_par_iter()
+        .map(|n| Some(n).filter(|n| n % 2 == 0))
+        .par_oks()
+        .collect();
+    assert_eq!(evens.len(), (N / 2) as usize);
+
+    let records = || {
+        (0..N).map(|n| {
+            if n % 3 == 0 {
+                Record::Invalid(n.to_string())
+            } else {
+                Record::Valid(n)
+            }
+        })
+    };
+    let mut rejected = vec![Record::Invalid("earlier".to_string())];
+    let valid = par_collect_inner!(records().collect::<Vec<_>>().into_par_iter(), if Record::Valid, into rejected);
+
+    let mut seq_rejected = vec![Record::Invalid("earlier".to_string())];
+    let seq_valid = collect_inner!(records(), if Record::Valid, into seq_rejected);
+    assert_eq!(valid, seq_valid);
+    assert_eq!(rejected, seq_rejected);
 }
 
 #[test]
-fn apple() {
+fn borrow_inner_mut() {
+    use std::cell::{RefCell, RefMut};
+
+    #[derive(Debug, PartialEq)]
     enum Fruit {
         Apple(i32),
-        _Orange(i16),
+        Orange(String),
     }
-    let z = Fruit::Apple(15);
-    assert_eq!(15, inner!(z, if Fruit::Apple));
+
+    let cell = RefCell::new(Fruit::Apple(3));
+    {
+        let mut apple: RefMut<i32> = borrow_inner_mut!(cell, if Fruit::Apple);
+        assert!(cell.try_borrow().is_err());
+        *apple *= 10;
+    }
+    assert_eq!(*cell.borrow(), Fruit::Apple(30));
+
+    *cell.borrow_mut() = Fruit::Orange("o".to_string());
+    borrow_inner_mut!(cell, if Fruit::Orange).push('k');
+    assert_eq!(*cell.borrow(), Fruit::Orange("ok".to_string()));
+
+    let mut fallback = RefCell::new(Fruit::Apple(0));
+    let mut n = borrow_inner_mut!(cell, if Fruit::Apple, else |e| {
+        assert_eq!(*e, Fruit::Orange("ok".to_string()));
+        borrow_inner_mut!(fallback, if Fruit::Apple)
+    });
+    *n = 5;
+    drop(n);
+    assert_eq!(*fallback.get_mut(), Fruit::Apple(5));
+    *cell.borrow_mut() = Fruit::Apple(1);
+
+    for _ in 0..1 {
+        let _orange = borrow_inner_mut!(cell, if Fruit::Orange, else continue);
+        unreachable!();
+    }
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = RefCell::new(Fruit::Apple(2));
+            borrow_inner_mut!(z, if Fruit::Orange);
+        }),
+        "Expected 'Fruit::Orange' inside 'z' (type try_utils::borrow_inner_mut::Fruit), found Apple(2)"
+    );
 }
 
 #[test]
-fn if_else() {
+fn collect_inner_as() {
+    use std::collections::{BTreeSet, VecDeque};
+
+    #[derive(Debug, PartialEq)]
     enum Fruit {
         Apple(i32),
-        _Orange(i16),
+        Orange(i16),
     }
-    let z = Fruit::Apple(15);
-    assert_eq!(15, inner!(z, if Fruit::Apple, else panic!("Not an apple")));
+
+    let basket = || {
+        vec![
+            Fruit::Apple(3),
+            Fruit::Orange(1),
+            Fruit::Apple(1),
+            Fruit::Apple(3),
+        ]
+    };
+
+    let apples = collect_inner!(basket(), if Fruit::Apple, as BTreeSet<i32>);
+    assert_eq!(apples.into_iter().collect::<Vec<_>>(), [1, 3]);
+
+    let mut rejected = VecDeque::new();
+    let apples = collect_inner!(basket(), if Fruit::Apple, into rejected, as Vec<i32>);
+    assert_eq!(apples, [3, 1, 3]);
+    assert_eq!(rejected, [Fruit::Orange(1)]);
 }
 
+#[cfg(feature = "heapless")]
 #[test]
-fn own_enum() {
-    #[derive(Debug, PartialEq, Eq)]
+fn collect_inner_heapless() {
+    #[derive(Debug, PartialEq)]
     enum Fruit {
         Apple(i32),
         Orange(i16),
     }
 
-    impl IntoResult<i32, i16> for Fruit {
-        fn into_result(self) -> Result<i32, i16> {
-            match self {
-                Fruit::Apple(i) => Ok(i),
-                Fruit::Orange(i) => Err(i),
+    let basket = || {
+        [
+            Fruit::Apple(1),
+            Fruit::Orange(2),
+            Fruit::Apple(3),
+            Fruit::Apple(4),
+        ]
+    };
+
+    let apples = collect_inner!(basket(), if Fruit::Apple, as heapless::Vec<i32, 4>);
+    assert_eq!(apples.unwrap(), [1, 3, 4]);
+    let apples = collect_inner!(basket(), if Fruit::Apple, as heapless::Vec<i32, 2>);
+    assert_eq!(apples.unwrap_err(), 4);
+
+    let mut rejected: heapless::Vec<Fruit, 4> = heapless::Vec::new();
+    let apples = collect_inner!(basket(), if Fruit::Apple, into rejected, as heapless::Vec<i32, 3>);
+    assert_eq!(apples.unwrap(), [1, 3, 4]);
+    assert_eq!(rejected, [Fruit::Orange(2)]);
+    let mut rejected: heapless::Vec<Fruit, 4> = heapless::Vec::new();
+    let apples = collect_inner!(basket(), if Fruit::Apple, into rejected, as heapless::Vec<i32, 1>);
+    assert_eq!(apples.unwrap_err(), 3);
+    assert_eq!(rejected, [Fruit::Orange(2)]);
+
+    let mut room: heapless::Vec<i32, 3> = heapless::Vec::new();
+    assert_eq!(
+        collect_inner!(basket(), if Fruit::Apple, try_push room),
+        Ok(())
+    );
+    assert_eq!(room, [1, 3, 4]);
+
+    let mut small: heapless::Vec<i32, 2> = heapless::Vec::new();
+    assert_eq!(
+        collect_inner!(basket(), if Fruit::Apple, try_push small),
+        Err(4)
+    );
+    assert_eq!(small, [1, 3]);
+
+    let mut room: heapless::Vec<i32, 3> = heapless::Vec::new();
+    let mut rejected: heapless::Vec<Fruit, 1> = heapless::Vec::new();
+    assert_eq!(
+        collect_inner!(basket(), if Fruit::Apple, try_push room, into rejected),
+        Ok(())
+    );
+    assert_eq!(room, [1, 3, 4]);
+    assert_eq!(rejected, [Fruit::Orange(2)]);
+
+    let mut small: heapless::Vec<i32, 1> = heapless::Vec::new();
+    let mut rejected: heapless::Vec<Fruit, 1> = heapless::Vec::new();
+    assert_eq!(
+        collect_inner!(basket(), if Fruit::Apple, try_push small, into rejected),
+        Err(Overflow::Payload(3))
+    );
+    assert_eq!(small, [1]);
+    assert_eq!(rejected, [Fruit::Orange(2)]);
+
+    let mut room: heapless::Vec<i32, 3> = heapless::Vec::new();
+    let mut none: heapless::Vec<Fruit, 0> = heapless::Vec::new();
+    assert_eq!(
+        collect_inner!(basket(), if Fruit::Apple, try_push room, into none),
+        Err(Overflow::Rejected(Fruit::Orange(2)))
+    );
+    assert_eq!(room, [1]);
+}
+
+#[test]
+fn rest_patterns() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Shape {
+        Triple(i32, String, f64),
+        Point,
+    }
+
+    let t = Shape::Triple(1, "two".to_string(), 3.0);
+    let first = inner!(t, if Shape::Triple(first, ..));
+    assert_eq!(first, 1);
+
+    let t = Shape::Triple(1, "two".to_string(), 3.0);
+    let (first, second) = inner!(t, if Shape::Triple(first, second, ..), else return);
+    assert_eq!((first, second.as_str()), (1, "two"));
+
+    let t = &Shape::Triple(4, String::new(), 0.5);
+    let first: &i32 = inner!(t, if Shape::Triple(first, ..));
+    assert_eq!(*first, 4);
+
+    assert_eq!(inner!(Shape::Point, if Shape::Triple(first, ..), else 0), 0);
+    let first = inner!(Shape::Point, if Shape::Triple(first, ..), else |e| {
+        assert!(matches!(e, Shape::Point));
+        -1
+    });
+    assert_eq!(first, -1);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Shape::Point;
+            inner!(z, if Shape::Triple(first, ..));
+        }),
+        "Expected 'Shape::Triple' inside 'z' (type try_utils::rest_patterns::Shape), found Point"
+    );
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn futures_stream() {
+    use core::future::Future;
+    use core::task::{Context, Poll, Waker};
+    use futures::stream::{self, StreamExt};
+
+    // The streams are always ready, so there is nothing to wake up for.
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = core::pin::pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+                return v;
             }
         }
     }
-    let z = Fruit::Orange(15);
-    assert_eq!(7, inner!(z, else |e| (e - 8) as i32));
 
-    let z = Fruit::Apple(15);
-    assert_eq!(
-        9,
-        inner!(z, if Fruit::Orange, else |e| {
-            assert_eq!(e, Fruit::Apple(15));
-            9
-        })
+    #[derive(Debug)]
+    enum Event {
+        Data(Vec<u8>),
+        Ping,
+        Closed(i32),
+    }
+
+    let events = || {
+        stream::iter(vec![
+            Event::Ping,
+            Event::Data(vec![1, 2]),
+            Event::Closed(0),
+            Event::Data(vec![3]),
+            Event::Ping,
+        ])
+    };
+
+    let data: Vec<Vec<u8>> = block_on(stream_filter_inner!(events(), if Event::Data).collect());
+    assert_eq!(data, [vec![1, 2], vec![3]]);
+
+    let mut codes = stream_filter_inner!(events(), if Event::Closed);
+    assert_eq!(block_on(codes.next()), Some(0));
+    assert_eq!(block_on(codes.next()), None);
+
+    let parsed = || stream::iter(["1", "x", "3"]).map(str::parse::<i32>);
+    let numbers: Vec<i32> = block_on(parsed().filter_inner().collect());
+    assert_eq!(numbers, [1, 3]);
+    let numbers: Vec<i32> = block_on(parsed().map_inner(|_e| 0).collect());
+    assert_eq!(numbers, [1, 0, 3]);
+
+    let somes: Vec<char> = block_on(
+        stream::iter([Some('a'), None, Some('b')])
+            .filter_inner()
+            .collect(),
     );
+    assert_eq!(somes, ['a', 'b']);
 }
 
 #[test]
-fn some() {
-    #[derive(Debug, PartialEq, Eq)]
-    enum Fruit {
-        Apple(i32),
-        Orange(i16),
+fn phantom_data_payload() {
+    use core::marker::PhantomData;
+
+    #[derive(Debug)]
+    struct Meters;
+
+    #[derive(Debug, PartialEq)]
+    enum Wrapper<T> {
+        Tagged(PhantomData<T>),
+        Unit(()),
+        Empty([u8; 0]),
+        Plain(u8),
     }
 
-    assert_eq!(some!(Fruit::Apple(15), if Fruit::Apple), Some(15));
-    assert_eq!(some!(Fruit::Orange(15), if Fruit::Apple), None);
+    let tag: PhantomData<Meters> =
+        inner!(Wrapper::Tagged(PhantomData::<Meters>), if Wrapper::Tagged);
+    assert_eq!(core::mem::size_of_val(&tag), 0);
+
+    let plain = Wrapper::<Meters>::Plain(1);
+    assert!(some!(plain, if Wrapper::Tagged).is_none());
+    let tag: &PhantomData<String> = inner!(&Wrapper::Tagged(PhantomData), if Wrapper::Tagged);
+    assert_eq!(*tag, PhantomData);
+    assert_eq!(inner!(Wrapper::<()>::Plain(2), if Wrapper::Plain), 2);
+
+    let () = inner!(Wrapper::<u8>::Unit(()), if Wrapper::Unit);
+    assert_eq!(inner!(&Wrapper::<u8>::Unit(()), if Wrapper::Unit), &());
+    let empty: [u8; 0] = inner!(Wrapper::<u8>::Empty([]), if Wrapper::Empty);
+    assert!(empty.is_empty());
     assert_eq!(
-        some!(Fruit::Orange(15), if Fruit::Apple, else |e| {
-            assert_eq!(e, Fruit::Orange(15));
-            Some(30)
+        ok!(Wrapper::<u8>::Plain(3), if Wrapper::Unit),
+        Err(Wrapper::Plain(3))
+    );
+    let units = collect_inner!([Wrapper::<u8>::Unit(()), Wrapper::Plain(4), Wrapper::Unit(())], if Wrapper::Unit, as Vec<()>);
+    assert_eq!(units.len(), 2);
+
+    let r: Result<(), ()> = Ok(());
+    let () = inner!(r);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z = Wrapper::<u8>::Unit(());
+            inner!(z, if Wrapper::Tagged);
         }),
-        Some(30)
+        "Expected 'Wrapper::Tagged' inside 'z' (type try_utils::phantom_data_payload::Wrapper<u8>), found Unit(())"
     );
 }
 
 #[test]
-fn ok() {
-    #[derive(Debug, PartialEq, Eq)]
-    enum Fruit {
-        Apple(i32),
-        Orange(i16),
+fn boxed_into_result() {
+    let boxed: Box<Option<String>> = Box::new(Some("apple".to_string()));
+    let s: String = inner!(boxed);
+    assert_eq!(s, "apple");
+
+    let boxed: Box<Option<i32>> = Box::new(None);
+    assert_eq!(inner!(boxed, else 7), 7);
+
+    let boxed: Box<Result<i32, String>> = Box::new(Err("rotten".to_string()));
+    assert_eq!(inner!(boxed, else |e| e.len() as i32), 6);
+
+    let nested: Box<Box<Option<u8>>> = Box::new(Box::new(Some(3)));
+    assert_eq!(inner!(nested), 3);
+    assert_eq!(inner!(Box::new(U32ToChar(0x61))), 'a');
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let z: Box<Option<i32>> = Box::new(None);
+            inner!(z);
+        }),
+        "Unexpected value found inside 'z' (type alloc::boxed::Box<core::option::Option<i32>>)"
+    );
+}
+
+#[test]
+fn first_inner_inline_vecs() {
+    use arrayvec::ArrayVec;
+    use smallvec::{smallvec, SmallVec};
+
+    let words: SmallVec<[String; 2]> = smallvec!["hello".to_string(), "world".to_string()];
+    assert_eq!(first_inner!(ref words, else return), "hello");
+    assert!(!words.spilled());
+    assert_eq!(first_inner!(words, else String::new()), "hello");
+
+    let spilled: SmallVec<[u8; 1]> = smallvec![3, 4, 5];
+    assert!(spilled.spilled());
+    assert_eq!(first_inner!(spilled, else 0), 3);
+
+    let empty: SmallVec<[u8; 4]> = SmallVec::new();
+    assert_eq!(*first_inner!(ref empty, else &9), 9);
+    assert_eq!(first_inner!(empty, else |_e| 9), 9);
+
+    let mut numbers: ArrayVec<i32, 4> = ArrayVec::new();
+    assert_eq!(first_inner!(ref numbers, else |_e| &0), &0);
+    numbers.push(7);
+    numbers.push(8);
+    assert_eq!(first_inner!(ref numbers, else |_e| &0), &7);
+    assert_eq!(first_inner!(numbers, else 0), 7);
+
+    let empty: ArrayVec<String, 2> = ArrayVec::new();
+    assert_eq!(first_inner!(empty, else "none".to_string()), "none");
+}
+
+#[test]
+fn fold_result() {
+    enum Reading {
+        Valid(u32),
+        Invalid(String),
     }
 
-    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple), Ok(15));
+    impl IntoResult<u32, String> for Reading {
+        fn into_result(self) -> Result<u32, String> {
+            match self {
+                Reading::Valid(n) => Ok(n),
+                Reading::Invalid(s) => Err(s),
+            }
+        }
+    }
+
+    let r: Result<i32, String> = Ok(2);
+    assert_eq!(fold_result!(r, |n| n * 10, |e| e.len() as i32), 20);
+    assert_eq!(fold_result!(None::<u8>, |n| n, |_e| 0), 0);
+
+    let mut sum = 0;
+    let mut errors = Vec::new();
+    let readings = vec![
+        Reading::Valid(1),
+        Reading::Invalid("bad".to_string()),
+        Reading::Valid(2),
+        Reading::Invalid("stop".to_string()),
+        Reading::Valid(100),
+    ];
+    for reading in readings {
+        let n = fold_result!(reading, |n| n, |e| {
+            if e == "stop" {
+                break;
+            }
+            errors.push(e);
+            continue;
+        });
+        sum += n;
+    }
+    assert_eq!((sum, errors), (3, vec!["bad".to_string()]));
+
+    fn parse_both(a: &str, b: &str) -> Result<i32, std::num::ParseIntError> {
+        let a = fold_result!(a.parse::<i32>(), |n| n, |_e| return Ok(-1));
+        let b = fold_result!(b.parse::<i32>(), |n| n, |e| Err(e)?);
+        Ok(a + b)
+    }
+    assert_eq!(parse_both("1", "2"), Ok(3));
+    assert_eq!(parse_both("x", "2"), Ok(-1));
+    assert!(parse_both("1", "y").is_err());
+}
+
+#[test]
+fn else_patterns() {
+    #[derive(Debug)]
+    struct HttpError {
+        code: u16,
+        reason: &'static str,
+    }
+
+    #[derive(Debug)]
+    enum Failure {
+        Timeout(u32),
+    }
 
+    let r: Result<u8, (i32, String)> = Err((3, "three".to_string()));
     assert_eq!(
-        ok!(Fruit::Orange(15), if Fruit::Apple),
-        Err(Fruit::Orange(15))
+        inner!(r, else |(code, msg)| { assert_eq!(msg, "three"); code as u8 }),
+        3
     );
+
+    let r: Result<&str, HttpError> = Err(HttpError {
+        code: 503,
+        reason: "busy",
+    });
     assert_eq!(
-        ok!(Fruit::Orange(15), if Fruit::Apple, else |e| {
-            assert_eq!(e, Fruit::Orange(15));
-            Err(3)
-        }),
-        Err(3)
+        inner!(r, else |HttpError { code, reason }| if code >= 500 { reason } else { "client" }),
+        "busy"
     );
 
-    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Orange, or 67), Err(67));
-    assert_eq!(ok!(Fruit::Apple(15), if Fruit::Apple, or 67), Ok(15));
+    let r: Result<u32, Failure> = Err(Failure::Timeout(30));
+    assert_eq!(inner!(r, else |Failure::Timeout(secs)| secs * 2), 60);
+
+    let r: Result<u32, (u32, u32)> = Err((1, 2));
+    assert_eq!(inner!(r, else |(a, _)| a), 1);
+
+    let r: Result<Vec<u8>, Vec<u8>> = Err(vec![1]);
+    let v = inner!(r, else |mut v| {
+        v.push(2);
+        v
+    });
+    assert_eq!(v, [1, 2]);
+
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+    enum Shape {
+        Circle(i32),
+    }
+    let y = inner!(if let Point { x: 0, y } = Point { x: 1, y: 2 } => y, else |Point { x, .. }| x);
+    assert_eq!(y, 1);
+    assert_eq!(
+        inner!(Shape::Circle(5), if Shape::Circle, guard |r| r > 9, else |Shape::Circle(r)| -r),
+        -5
+    );
+
+    let opt: Option<u8> = None;
+    assert_eq!(inner!(opt, err_with || ("missing", 7), else |(_, n)| n), 7);
+
+    assert_eq!(fold_result!(r_pair(), |(a, b)| a + b, |(code, _)| code), 5);
+    fn r_pair() -> Result<(i32, i32), (i32, String)> {
+        Ok((2, 3))
+    }
+
+    type Pair = Result<u8, (u8, u8)>;
+    let pair: Pair = Err((1, 2));
+    assert_eq!(some!(pair, if Ok, else |Err((a, _))| Some(a)), Some(1));
+    assert_eq!(some!(pair, if Ok, else_some |Err((_, b))| b), Some(2));
+    assert_eq!(
+        ok!(pair, if Ok, else |Err((a, b))| Ok::<_, ()>(a + b)),
+        Ok(3)
+    );
+    assert_eq!(ok!(pair, if Ok, or |Err((a, _))| a), Err(1));
+    assert_eq!(
+        swap_variant!(pair, Ok => Some, else |Err((a, _))| Some(a)),
+        Some(1)
+    );
+    let nested: Result<Option<u8>, (u8, u8)> = Err((1, 2));
+    assert_eq!(ok_flat!(nested, if Ok, or |Err((_, _))| ()), Err(()));
+    assert_eq!(
+        transpose_variant!(nested, if Ok, else |Err((a, _))| Some(a)),
+        Some(1)
+    );
+
+    let mut slot: Pair = Err((4, 5));
+    assert_eq!(replace_inner!(&mut slot, if Ok, Ok(0), else |_| 5), 5);
+
+    let r: Result<Box<u8>, (u8, u8)> = Err((6, 7));
+    assert_eq!(deref_inner!(r, else |(_, b)| b), 7);
+
+    let cell = core::cell::RefCell::new(slot);
+    let first = || -> u8 { *borrow_inner!(cell, if Ok, else |_| return 0) };
+    assert_eq!(first(), 0);
+}
+
+#[test]
+fn replace_inner() {
+    #[derive(Debug, PartialEq)]
+    enum State {
+        Running(String),
+        Paused(u32),
+        Stopped,
+    }
+
+    struct Machine {
+        state: State,
+    }
+
+    let mut m = Machine {
+        state: State::Running("job".to_string()),
+    };
+    let job = replace_inner!(&mut m.state, if State::Running, State::Paused(1));
+    assert_eq!(job, "job");
+    assert_eq!(m.state, State::Paused(1));
+
+    let mut built = 0;
+    let job = replace_inner!(&mut m.state, if State::Running, { built += 1; State::Stopped }, else String::new());
+    assert_eq!((job.as_str(), built), ("", 0));
+    assert_eq!(m.state, State::Paused(1));
+
+    let n = replace_inner!(&mut m.state, if State::Running, State::Stopped, else |e| {
+        *e = State::Paused(2);
+        String::from("paused")
+    });
+    assert_eq!(n, "paused");
+    assert_eq!(m.state, State::Paused(2));
+
+    assert_eq!(
+        replace_inner!(&mut m.state, if State::Paused, State::Stopped),
+        2
+    );
+    assert_eq!(m.state, State::Stopped);
+
+    #[cfg(not(feature = "defmt"))]
+    assert_eq!(
+        panic_message(|| {
+            let mut z = State::Stopped;
+            replace_inner!(&mut z, if State::Paused, State::Stopped);
+        }),
+        "Expected 'State::Paused' inside '&mut z' (type try_utils::replace_inner::State), found Stopped"
+    );
 }
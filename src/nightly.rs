@@ -0,0 +1,30 @@
+//! `IntoResult` as a `const` trait, for the `nightly` feature.
+
+use core::marker::Destruct;
+
+/// Converts a value into a Result.
+/// You can implement this for your own types if you want
+/// to use the `inner!` macro in more ergonomic ways.
+///
+/// With the `nightly` feature, implementations can be `const`, which lets
+/// the forms of `inner!` without an `if` clause be used in `const fn`.
+pub const trait IntoResult<T, E> {
+    fn into_result(self) -> Result<T, E>;
+}
+
+impl<T, E> const IntoResult<T, E> for Result<T, E> {
+    #[inline]
+    fn into_result(self) -> Result<T, E> {
+        self
+    }
+}
+
+impl<T: [const] Destruct> const IntoResult<T, ()> for Option<T> {
+    #[inline]
+    fn into_result(self) -> Result<T, ()> {
+        match self {
+            Some(v) => Ok(v),
+            None => Err(()),
+        }
+    }
+}
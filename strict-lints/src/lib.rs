@@ -0,0 +1,194 @@
+//! Uses every form of the `try_utils` macros under strict lints. CI runs
+//! clippy on this crate, so a warning from an expansion fails the build.
+
+#![deny(warnings)]
+#![warn(clippy::pedantic, unreachable_code, unused, rust_2018_idioms)]
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::BuildHasher;
+use std::rc::Rc;
+use try_utils::{
+    any_inner, assert_inner_eq, borrow_inner, borrow_inner_mut, collect_inner, const_inner,
+    deref_inner, ensure_inner, extract_or_break, extract_or_continue, first_inner, fold_result,
+    inner, inner_cow, inner_first, inner_test, inner_try, inner_upgrade, inner_zip,
+    match_any_inner, ok, ok_flat, replace_inner, scan_inner, select_variant, select_variant_mut,
+    some, split_head_inner, swap_variant, transpose_variant,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fruit {
+    Apple(i32),
+    Orange(i16),
+    Pair((i32, &'static str)),
+}
+
+// rustfmt would space out the closure-like clauses, `|v|` into `| v |`.
+#[rustfmt::skip::macros(inner)]
+#[must_use]
+pub fn inner_forms(fruit: Fruit, opt: Option<i32>, res: Result<i32, String>) -> i32 {
+    let mut sum = inner!(opt);
+    sum += inner!(opt, else return 0);
+    sum += inner!(opt, else |_e| 1);
+    sum += inner!(res.clone(), else |e| return i32::from(e.is_empty()));
+    sum += inner!(res, else return 2);
+    sum += inner!(opt, filter |v| v > 0, else 3);
+    sum += inner!(opt, filter |v| v > 0, else |_e| 4);
+    sum += inner!(opt, filter |v| v > 0);
+    sum += inner!(opt, err_with || "missing", else |e| return i32::from(e.is_empty()));
+    sum + variant_forms(fruit)
+}
+
+#[must_use]
+pub fn variant_forms(fruit: Fruit) -> i32 {
+    let mut sum = match fruit {
+        Fruit::Apple(n) => inner!(Fruit::Apple(n), if Fruit::Apple),
+        Fruit::Orange(n) => i32::from(inner!(Fruit::Orange(n), if Fruit::Orange, else return 0)),
+        Fruit::Pair(p) => inner!(Fruit::Pair(p), if Fruit::Pair((n, _s)), else |_e| return 0).0,
+    };
+    sum += inner!(Fruit::Apple(sum), if Fruit::Apple, else |_e| return 0);
+    sum += inner!(Fruit::Apple(sum), if Fruit::Apple, guard |n| n > 0, else 0);
+    sum += inner!(Fruit::Apple(sum), if Fruit::Apple, guard |n| n > 0, else |_e| 0);
+    sum += inner!(Fruit::Apple(sum), if Fruit::Apple, guard |n| n > 0);
+    sum += some!(Fruit::Apple(sum), if Fruit::Apple, guard |n| n > 0).unwrap_or(0);
+    sum += ok!(Fruit::Apple(sum), if Fruit::Apple, guard |n| n > 0).unwrap_or(0);
+    sum += inner!(Fruit::Pair((sum, "")), if Fruit::Pair((n, s))).0;
+    sum += inner!(Fruit::Pair((sum, "")), if Fruit::Pair((n, s)), else (0, "")).0;
+    sum += inner!(if let Fruit::Apple(n) = Fruit::Apple(sum) => n + 1, else 0);
+    sum += inner!(Fruit::Apple(sum), if let Fruit::Apple(n) = Fruit::Apple(sum), else 0);
+    sum += inner!(if let Fruit::Apple(n) = Fruit::Apple(sum) => n + 1, else |_e| 0);
+    sum + inner!(if let Fruit::Apple(n) = Fruit::Apple(sum) => n + 1)
+}
+
+/// # Panics
+/// Never, the apples are all positive.
+#[must_use]
+pub fn other_macros<S: BuildHasher>(fruit: Fruit, map: &HashMap<&str, i32, S>) -> Option<i32> {
+    let _ = some!(Fruit::Apple(1), if Fruit::Apple);
+    let _ = some!(map.get("a"));
+    let _ = some!(Fruit::Apple(1), if Fruit::Apple, else_some 0);
+    let _ = some!(Fruit::Apple(1), if Fruit::Apple, else_some return None);
+    let _ = some!(Fruit::Apple(1), if Fruit::Apple, else_some |_e| return None);
+    let _: Result<i32, Fruit> = ok!(Fruit::Apple(1), if Fruit::Apple);
+    let _: Result<i32, i32> = ok!(Fruit::Apple(1), if Fruit::Apple, or 0);
+    let _: Result<i32, String> = ok!(Fruit::Apple(1), if Fruit::Apple, or_fmt "no apple in {}", 1);
+    let _: Result<i32, Fruit> = ok!(Fruit::Apple(1), if Fruit::Apple, else |e| Err(e));
+    let _: Result<i32, String> = ok_flat!(Some(Ok(1)), if Some, or String::new());
+    let _ = match_any_inner!(Fruit::Orange(1), { Fruit::Apple, Fruit::Orange } into i64, else 0);
+    let _ = swap_variant!(Some(1), Some => Ok, else |e| Err::<i32, Option<i32>>(e));
+    let _ = first_inner!([1, 2], else 0);
+    let _ = inner_test!(Fruit::Apple(1), if Fruit::Apple, |n: i32| assert!(n > 0));
+    let _ = split_head_inner!(Fruit::Apple(1), if Fruit::Apple, rest 2);
+    let _ = deref_inner!(Some(Box::new(1)), else 0);
+    let _ = inner_upgrade!(Rc::downgrade(&Rc::new(Fruit::Apple(1))), if Fruit::Apple, else 0);
+    let _ = any_inner!(Box::new(1) as Box<dyn std::any::Any>, as i32, else 0);
+    let mut rejected = Vec::new();
+    let v: Vec<i32> = collect_inner!([Fruit::Apple(1)], if Fruit::Apple, into rejected);
+    ensure_inner!(fruit, if Fruit::Apple, else return None);
+    assert_inner_eq!(Fruit::Apple(1), Fruit::Apple, 1);
+    let (x, y) = inner_zip!(Some(1), Some(2), else return None);
+    Some(x + y + i32::try_from(v.len()).ok()? + i32::try_from(rejected.len()).ok()?)
+}
+
+/// # Errors
+/// Gives back anything but an apple.
+pub fn trying(fruit: Fruit) -> Result<i32, Fruit> {
+    let n = inner_try!(ok!(fruit, if Fruit::Apple));
+    Ok(n)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Triple(i32, i32, i32),
+    Label(String),
+    Cached(Option<i32>),
+    Empty,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event {
+    Click(Point),
+    Key(char),
+}
+
+const EVENTS: &[Event] = &[Event::Click(Point { x: 1, y: 2 }), Event::Key('q')];
+const FIRST_CLICK: Point = const_inner!(EVENTS[0], if Event::Click);
+
+// Kept from rustfmt for the same reason as `inner_forms`.
+#[rustfmt::skip::macros(inner)]
+#[must_use]
+pub fn clause_forms(ev: &Event, shape: &Shape, res: Result<i32, (i32, String)>) -> i32 {
+    let mut sum = inner!(ev, if Event::Click, .x);
+    sum += inner!(ev, if Event::Click, .y, else 0);
+    let x: &i32 = inner!(ev, if Event::Click, ref .x);
+    let y: &i32 = inner!(ev, if Event::Click, ref .y, else return 0);
+    sum += x + y + FIRST_CLICK.x;
+    sum += inner!(Some(sum), then |v| v * 2);
+    sum += inner!(Some(sum), then |v| v + 1, else 0);
+    sum += inner!(shape.clone(), if Shape::Label, then |l| i32::try_from(l.len()).unwrap_or(0), else |_e| 0);
+    sum += inner!(shape.clone(), if Shape::Triple(a, ..), else 0);
+    let (a, b) = inner!(shape.clone(), if Shape::Triple(a, b, ..), else |_e| (0, 0));
+    sum += a + b;
+    sum += inner!(res.clone(), else |(code, _)| code);
+    sum += inner!(res, if Ok, else |Err((code, msg))| code + i32::try_from(msg.len()).unwrap_or(0));
+    sum += inner!(Err::<i32, Point>(FIRST_CLICK), else |Point { x, .. }| x);
+    sum + inner!(shape.clone(), if Shape::Cached, guard |c| c.is_some(), else |_| None).unwrap_or(0)
+}
+
+/// # Errors
+/// Gives back anything but a label, boxed.
+pub fn boxed(shape: Shape) -> Result<String, Box<Shape>> {
+    ok!(shape, if Shape::Label, boxed_err)
+}
+
+/// # Panics
+/// If the cell does not hold a label.
+#[must_use]
+pub fn newer_macros(shapes: &mut [Shape], cell: &RefCell<Shape>, state: &mut Shape) -> Option<i32> {
+    let _: Cow<'_, str> = inner_cow!(&shapes[0], if Shape::Label);
+    let _: Cow<'_, str> = inner_cow!(&shapes[0], if Shape::Label, else "none");
+    let _ = inner_first!(shapes[0].clone(), [Shape::Cached, Shape::Label => |l: String| i32::try_from(l.len()).ok()], else None);
+    let _ = inner_first!(Event::Key('a'), [Event::Key => u32::from] into u64, else |_e| 0);
+    let _ = transpose_variant!(Shape::Cached(Some(1)), if Shape::Cached);
+    let _ = transpose_variant!(Shape::Empty, if Shape::Cached, else Some(0));
+    let _ = transpose_variant!(Shape::Empty, if Shape::Cached, else |_e| None);
+    let _ = transpose_variant!(Some(1), into Shape::Cached);
+    let _ = transpose_variant!(None, into Shape::Cached, or Shape::Empty);
+    let mut total = 0;
+    for shape in shapes.iter() {
+        total += extract_or_continue!(shape, if Shape::Cached).unwrap_or(0);
+        total += extract_or_break!(Some(1));
+    }
+    for shape in shapes.iter() {
+        total += extract_or_break!(shape, if Shape::Cached).unwrap_or(0);
+        total += extract_or_continue!(Some(1));
+    }
+    let labels: Vec<&String> = select_variant!(shapes, if Shape::Label, guard |l| !l.is_empty());
+    total += i32::try_from(labels.len()).ok()?;
+    for cached in select_variant_mut!(shapes, if Shape::Cached) {
+        cached.take();
+    }
+    let _ = select_variant_mut!(shapes, if Shape::Label, guard |l| l.is_empty());
+    let _ = select_variant!(shapes, if Shape::Label);
+    total += *scan_inner!(ref shapes[..], if Shape::Cached)?.as_ref()?;
+    total += scan_inner!([Some(1), None], if Some)?;
+    total += i32::try_from(borrow_inner!(cell, if Shape::Label).len()).ok()?;
+    let _ = borrow_inner!(cell, if Shape::Label, else return None);
+    let _ = borrow_inner!(cell, if Shape::Label, else |_e| return None);
+    borrow_inner_mut!(cell, if Shape::Label).push('!');
+    borrow_inner_mut!(cell, if Shape::Label, else return None).push('!');
+    borrow_inner_mut!(cell, if Shape::Label, else |_e| return None).push('!');
+    total += fold_result!("1".parse::<i32>(), |n| n, |_e| return None);
+    let _ = replace_inner!(&mut *state, if Shape::Label, Shape::Empty, else |_e| String::new());
+    let _ = replace_inner!(&mut *state, if Shape::Label, Shape::Empty, else String::new());
+    let _ = replace_inner!(&mut *state, if Shape::Cached, Shape::Empty);
+    Some(total)
+}
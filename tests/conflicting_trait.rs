@@ -0,0 +1,42 @@
+//! A trait of the caller's named `IntoResult`, with a method of the same
+//! name, must not get in the way of the macros.
+
+use try_utils::{inner, ok, some};
+
+trait IntoResult {
+    fn into_result(self) -> &'static str;
+}
+
+impl<T> IntoResult for Option<T> {
+    fn into_result(self) -> &'static str {
+        "mine"
+    }
+}
+
+impl<T, E> IntoResult for Result<T, E> {
+    fn into_result(self) -> &'static str {
+        "mine"
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Fruit {
+    Apple(i32),
+    Orange(i16),
+}
+
+#[test]
+fn own_trait_in_scope() {
+    assert_eq!(Some(1).into_result(), "mine");
+
+    assert_eq!(inner!(Some(1)), 1);
+    assert_eq!(inner!(None::<i32>, else 2), 2);
+    let r: Result<i32, &str> = Err("bad");
+    assert_eq!(inner!(r, else |e| e.len() as i32), 3);
+    assert_eq!(some!(Ok::<i32, ()>(4)), Some(4));
+    assert_eq!(
+        ok!(Fruit::Orange(5), if Fruit::Apple),
+        Err(Fruit::Orange(5))
+    );
+    assert_eq!(inner!(Fruit::Apple(6), if Fruit::Apple), 6);
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "derive")]
+
+use try_utils::*;
+
+#[derive(Debug, PartialEq, Eq, IntoResult)]
+enum Fruit {
+    #[ok(into)]
+    Apple(i32),
+    #[ok(into)]
+    Orange(i16),
+    Rotten,
+}
+
+#[derive(Debug, PartialEq, Eq, IntoResult)]
+#[into_result(ok = u64)]
+enum Reading<T> {
+    #[ok(into)]
+    Small(u8),
+    #[ok]
+    Large(u64),
+    Other(T),
+}
+
+#[test]
+fn into_variants() {
+    assert_eq!(inner!(Fruit::Apple(15)), 15i32);
+    assert_eq!(inner!(Fruit::Orange(9)), 9i32);
+    assert_eq!(Fruit::Rotten.into_result(), Err(Fruit::Rotten));
+
+    let y = inner!(Fruit::Rotten, else |e| {
+        assert_eq!(e, Fruit::Rotten);
+        0
+    });
+    assert_eq!(y, 0);
+}
+
+#[test]
+fn explicit_ok_type() {
+    assert_eq!(inner!(Reading::<()>::Small(3)), 3u64);
+    assert_eq!(inner!(Reading::<()>::Large(300)), 300u64);
+    assert_eq!(Reading::Other("x").into_result(), Err(Reading::Other("x")));
+}
+
+impl_inner_ext!(Fruit, Apple, i32);
+impl_inner_ext!(pub Reading<()>, Large, u64);
+
+#[test]
+fn inner_ext() {
+    let fruits = vec![
+        Fruit::Apple(1),
+        Fruit::Orange(2),
+        Fruit::Rotten,
+        Fruit::Apple(3),
+    ];
+    let apples: Vec<i32> = fruits.into_iter().apples().collect();
+    assert_eq!(apples, [1, 3]);
+
+    let readings = vec![Reading::Large(400), Reading::Small(1), Reading::Other(())];
+    assert_eq!(readings.into_iter().larges().sum::<u64>(), 400);
+}
@@ -0,0 +1,83 @@
+//! The macros must expand to fully qualified paths, so they work where the
+//! prelude is missing or its names are shadowed, as in `no_std` crates.
+
+#![no_implicit_prelude]
+
+extern crate std;
+// The defmt forms expand to `::defmt` paths, like defmt's own macros.
+#[cfg(feature = "defmt")]
+extern crate defmt;
+extern crate try_utils;
+
+use std::assert_eq;
+use try_utils::*;
+
+// The expansions must not pick up a `panic!` of the caller's either.
+#[allow(unused_macros)]
+macro_rules! panic {
+    ($($t:tt)*) => {
+        compile_error!("an expansion used the caller's panic!")
+    };
+}
+
+#[allow(dead_code)]
+#[derive(std::fmt::Debug)]
+enum Fruit {
+    Apple(i32),
+    Orange(i16),
+}
+
+fn apple(f: Fruit) -> std::option::Option<i32> {
+    std::option::Option::Some(inner!(f, if Fruit::Apple, else return std::option::Option::None))
+}
+
+#[test]
+fn option_and_result() {
+    assert_eq!(inner!(std::option::Option::Some(3)), 3);
+    assert_eq!(inner!(std::option::Option::None::<i32>, else 7), 7);
+    let r: std::result::Result<i32, ()> = std::result::Result::Err(());
+    assert_eq!(inner!(r, else |_e| 1), 1);
+    assert_eq!(
+        inner!(std::option::Option::Some(-3), filter |v| v > 0, else 0),
+        0
+    );
+}
+
+#[test]
+fn variants() {
+    assert_eq!(apple(Fruit::Apple(4)), std::option::Option::Some(4));
+    assert_eq!(apple(Fruit::Orange(4)), std::option::Option::None);
+    assert_eq!(
+        some!(Fruit::Orange(2), if Fruit::Orange),
+        std::option::Option::Some(2)
+    );
+    assert_eq!(inner!(Fruit::Apple(5), if Fruit::Apple), 5);
+    let r: std::result::Result<i32, Fruit> = ok!(Fruit::Apple(1), if Fruit::Apple);
+    assert_eq!(inner!(r, else |_e| 0), 1);
+}
+
+#[test]
+fn assertions() {
+    assert_inner_eq!(Fruit::Apple(3), Fruit::Apple, 3);
+    let r: std::result::Result<(), std::string::String> = std::panic::catch_unwind(|| {
+        assert_inner_eq!(Fruit::Orange(3), Fruit::Apple, 3);
+    })
+    .map_err(|_| std::string::String::new());
+    std::assert!(r.is_err());
+}
+
+#[test]
+fn read_file() {
+    std::assert!(inner_file!("Cargo.toml").contains("try_utils"));
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn event() {
+    assert_eq!(
+        inner!(Fruit::Orange(1), if Fruit::Apple, event warn, else 0),
+        0
+    );
+    let r: std::result::Result<i32, ()> = std::result::Result::Err(());
+    assert_eq!(inner!(r, event debug, else |_e| 1), 1);
+}
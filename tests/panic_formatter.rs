@@ -0,0 +1,60 @@
+//! The panic formatter is process wide and can only be set once, so it is
+//! tested on its own rather than next to the default messages.
+#![cfg(not(feature = "defmt"))]
+
+use std::panic;
+use try_utils::*;
+
+fn panic_message<F: FnOnce() + panic::UnwindSafe>(f: F) -> String {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(f);
+    panic::set_hook(previous);
+    let payload = result.unwrap_err();
+    match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => payload.downcast::<&str>().unwrap().to_string(),
+    }
+}
+
+fn json_lines(cx: &PanicContext<'_>) -> String {
+    format!(
+        r#"{{"incident":42,"expr":"{}","expected":{:?},"type":"{}","found":{:?},"line":{}}}"#,
+        cx.expr,
+        cx.expected,
+        cx.type_name,
+        cx.found.map(|f| format!("{:?}", f)),
+        cx.location.line()
+    )
+}
+
+#[test]
+fn custom_formatter() {
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    set_panic_formatter(json_lines).unwrap();
+    assert!(set_panic_formatter(json_lines).is_err());
+
+    let msg = panic_message(|| {
+        let z = Fruit::Orange(3);
+        inner!(z, if Fruit::Apple);
+    });
+    assert_eq!(
+        msg,
+        format!(
+            r#"{{"incident":42,"expr":"z","expected":Some("Fruit::Apple"),"type":"panic_formatter::custom_formatter::Fruit","found":Some("Orange(3)"),"line":{}}}"#,
+            line!() - 6
+        )
+    );
+
+    let msg = panic_message(|| {
+        let z: Option<i32> = None;
+        inner!(z);
+    });
+    assert!(msg.starts_with(r#"{"incident":42,"expr":"z","expected":None,"type":"core::option::Option<i32>","found":None"#), "{}", msg);
+}
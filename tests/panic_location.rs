@@ -0,0 +1,90 @@
+//! The panicking forms of `inner!` must report the location of the macro
+//! invocation, also in release builds. CI runs this with `--release` too.
+
+use std::panic;
+use std::sync::{Arc, Mutex};
+use try_utils::*;
+
+// The panic hook is process-wide, so the tests take turns with it.
+static HOOK: Mutex<()> = Mutex::new(());
+
+fn panic_location<F: FnOnce() + panic::UnwindSafe>(f: F) -> (String, u32) {
+    let _turn = HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    let location = Arc::new(Mutex::new(None));
+    let hook_location = location.clone();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let l = info.location().unwrap();
+        *hook_location.lock().unwrap() = Some((l.file().to_string(), l.line()));
+    }));
+    let result = panic::catch_unwind(f);
+    panic::set_hook(previous);
+    assert!(result.is_err());
+    let location = location.lock().unwrap().take();
+    location.unwrap()
+}
+
+#[test]
+fn reports_caller_location() {
+    #[allow(dead_code)]
+    enum Fruit {
+        Apple(i32),
+        Orange(i16),
+    }
+
+    let (file, line) = panic_location(|| {
+        let z: Option<i32> = None;
+        inner!(z);
+    });
+    assert!(file.ends_with("panic_location.rs"), "{}", file);
+    assert_eq!(line, line!() - 3);
+
+    let (file, line) = panic_location(|| {
+        let z = Fruit::Orange(3);
+        inner!(z, if Fruit::Apple);
+    });
+    assert!(file.ends_with("panic_location.rs"), "{}", file);
+    assert_eq!(line, line!() - 3);
+}
+
+#[test]
+fn into_result_location() {
+    struct Even(u32);
+
+    impl IntoResult<u32, u32> for Even {
+        fn into_result(self) -> Result<u32, u32> {
+            if self.0 % 2 == 0 {
+                Ok(self.0)
+            } else {
+                Err(self.0)
+            }
+        }
+    }
+
+    let (file, line) = panic_location(|| {
+        let r: Result<i32, &str> = Err("bad");
+        inner!(r);
+    });
+    assert!(file.ends_with("panic_location.rs"), "{}", file);
+    assert_eq!(line, line!() - 3);
+
+    let (_, line) = panic_location(|| {
+        inner!(Even(3));
+    });
+    assert_eq!(line, line!() - 2);
+}
+
+// The location passes on through `#[track_caller]` functions of the caller.
+#[track_caller]
+fn required(value: Option<u8>) -> u8 {
+    inner!(value)
+}
+
+#[test]
+fn through_track_caller() {
+    let (file, line) = panic_location(|| {
+        required(None);
+    });
+    assert!(file.ends_with("panic_location.rs"), "{}", file);
+    assert_eq!(line, line!() - 3);
+}
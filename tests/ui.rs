@@ -0,0 +1,8 @@
+//! Pins the compile errors for common mistakes in macro invocations.
+//! Regenerate the expected output with `TRYBUILD=overwrite cargo test --test ui`.
+
+#[test]
+fn ui() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
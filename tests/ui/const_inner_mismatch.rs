@@ -0,0 +1,14 @@
+use try_utils::const_inner;
+
+#[allow(dead_code)]
+enum Entry {
+    Timeout(u64),
+    Retries(u32),
+}
+
+const DEFAULTS: &[Entry] = &[Entry::Timeout(30), Entry::Retries(3)];
+const DEFAULT_TIMEOUT: u64 = const_inner!(DEFAULTS[1], if Entry::Timeout);
+
+fn main() {
+    let _ = DEFAULT_TIMEOUT;
+}
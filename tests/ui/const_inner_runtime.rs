@@ -0,0 +1,12 @@
+use try_utils::const_inner;
+
+#[allow(dead_code)]
+enum Entry {
+    Timeout(u64),
+    Retries(u32),
+}
+
+fn main() {
+    let entry = Entry::Retries(3);
+    let _ = const_inner!(entry, if Entry::Retries);
+}
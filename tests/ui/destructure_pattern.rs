@@ -0,0 +1,15 @@
+use try_utils::inner;
+
+enum E {
+    Pair((i32, String)),
+    Triple(i32, i32, i32),
+}
+
+fn main() {
+    let e = E::Pair((1, String::new()));
+    let _ = inner!(e, if E::Pair((a, _)));
+    let _ = inner!(e, if E::Pair((ref a, s)));
+    let _ = inner!(e, if E::Pair((1, s)));
+    let _ = inner!(e, if E::Pair((mut a, s)));
+    let _ = inner!(e, if E::Triple(a, .., c));
+}
@@ -0,0 +1,12 @@
+use try_utils::inner;
+
+enum Fruit {
+    Apple(i32),
+    Orange(i16),
+}
+
+fn main() {
+    let x = Fruit::Orange(1);
+    let _ = inner!(x, else return, if Fruit::Apple);
+    let _ = Fruit::Apple(0);
+}
@@ -0,0 +1,15 @@
+use try_utils::inner;
+
+struct Apple {
+    freshness: i32,
+}
+
+enum Fruit {
+    Apple(Apple),
+    Orange(i16),
+}
+
+fn main() {
+    let x = Fruit::Orange(3);
+    let _ = inner!(x, if Fruit::Apple, guard x.freshness > 0, else Apple { freshness: 0 });
+}
@@ -0,0 +1,17 @@
+use try_utils::inner;
+
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn main() {
+    let x = Some(20);
+    let _ = inner!(v + 1, if let Some(v) = x, else 0);
+
+    let p = Point { x: 1, y: 2 };
+    let _ = inner!(p, if let Point { x, .. } = p);
+
+    let y = Some(3);
+    let _ = inner!(x, if let Some(v) = y, else 0);
+}
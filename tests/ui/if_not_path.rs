@@ -0,0 +1,6 @@
+use try_utils::inner;
+
+fn main() {
+    let x: Option<i32> = None;
+    let _ = inner!(x, if 5);
+}